@@ -19,6 +19,7 @@ mod events;
 mod provider;
 mod state;
 mod subscription;
+mod webtransport;
 
 const EXTENSION_PORT_NAME: &str = "frame_connect";
 const CLIENT_STATUS_ALARM_KEY: &str = "check-client-status";
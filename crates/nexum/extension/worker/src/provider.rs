@@ -4,6 +4,7 @@ use std::sync::{
 };
 use std::time::Duration;
 
+use alloy_chains::Chain;
 use async_lock::RwLock;
 use futures::{
     StreamExt,
@@ -11,17 +12,34 @@ use futures::{
 };
 use gloo_timers::future::{IntervalStream, TimeoutFuture};
 use jsonrpsee::{
-    core::{client::ClientT, traits::ToRpcParams},
+    core::{
+        client::{ClientBuilder, ClientT, SubscriptionClientT},
+        traits::ToRpcParams,
+    },
+    rpc_params,
     wasm_client::{Client, WasmClientBuilder},
 };
 use nexum_chrome_gloo::tabs::QueryQueryInfo;
+use nexum_primitives::ChainStatus;
+use serde::Deserialize;
 use tracing::{debug, trace, warn};
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::spawn_local;
 
-use crate::{ConnectionState, Extension, events::send_event};
+use crate::{ConnectionState, Extension, events::send_event, webtransport};
 
 const UPSTREAM_URL: &str = "ws://127.0.0.1:1250/mainnet";
+/// Where the wallet tells how to reach its WebTransport endpoint, if it serves one
+const WEBTRANSPORT_INFO_URL: &str = "http://127.0.0.1:1250/webtransport";
+const WEBTRANSPORT_HOST: &str = "127.0.0.1";
+const WEBTRANSPORT_CHAIN: &str = "mainnet";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The parts of the wallet status sent by `nexum_subscribeStatus` that the extension shows
+#[derive(Deserialize)]
+struct WalletStatus {
+    chains: Vec<ChainStatus>,
+}
 
 pub struct Provider {
     client: RwLock<Option<Client>>,
@@ -111,6 +129,7 @@ impl Provider {
 
         send_event("connect", None, &QueryQueryInfo::new()).await;
         self.send_buffered_requests().await;
+        spawn_local(self.watch_status());
     }
 
     /// Executes logic when the provider disconnects
@@ -123,6 +142,48 @@ impl Provider {
         send_event("disconnect", None, &QueryQueryInfo::new()).await;
     }
 
+    /// Keeps the available chains of the frame state in sync with the chains the wallet serves,
+    /// until the client disconnects
+    async fn watch_status(self: Arc<Self>) {
+        let subscription = {
+            let guard = self.client.read().await;
+            let Some(client) = &*guard else {
+                return;
+            };
+            client
+                .subscribe::<WalletStatus, _>(
+                    "nexum_subscribeStatus",
+                    rpc_params![],
+                    "nexum_unsubscribeStatus",
+                )
+                .await
+        };
+        let mut subscription = match subscription {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                warn!(error = ?e, "Failed to subscribe to the wallet status");
+                return;
+            }
+        };
+
+        while let Some(status) = subscription.next().await {
+            match status {
+                Ok(status) => {
+                    let chains = status
+                        .chains
+                        .into_iter()
+                        .map(|chain| (Chain::from_id(chain.chain_id), chain.connected))
+                        .collect();
+                    self.extension.state.lock().await.set_chains(chains);
+                }
+                Err(e) => {
+                    warn!(error = ?e, "Invalid wallet status");
+                    break;
+                }
+            }
+        }
+    }
+
     /// Helper function to verify the connection by making a lightweight RPC call
     async fn verify_connection(&self) -> bool {
         let guard = self.client.read().await;
@@ -268,10 +329,21 @@ impl Provider {
     }
 }
 
-/// Creates a new JSON-RPC client with a timeout of 60 seconds
+/// Creates a new JSON-RPC client with a timeout of 60 seconds, connected over WebTransport or,
+/// if the wallet doesn't serve it or the browser doesn't support it, over WebSocket
 async fn create_client() -> Result<Client, JsValue> {
+    match webtransport::connect(WEBTRANSPORT_INFO_URL, WEBTRANSPORT_HOST, WEBTRANSPORT_CHAIN).await
+    {
+        Ok((sender, receiver)) => {
+            return Ok(ClientBuilder::default()
+                .request_timeout(REQUEST_TIMEOUT)
+                .build_with_wasm(sender, receiver));
+        }
+        Err(e) => debug!("Falling back to WebSocket: {e}"),
+    }
+
     WasmClientBuilder::default()
-        .request_timeout(Duration::from_secs(60))
+        .request_timeout(REQUEST_TIMEOUT)
         .build(UPSTREAM_URL)
         .await
         .map_err(|e| JsValue::from_str(&format!("Failed to create provider: {e:?}")))
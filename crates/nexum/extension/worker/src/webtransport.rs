@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
+use serde::Deserialize;
+use wasm_bindgen::{JsCast, prelude::*};
+use wasm_bindgen_futures::JsFuture;
+
+// The bindings of `WebTransport` in web-sys are unstable, only the few parts used are bound here
+#[wasm_bindgen]
+extern "C" {
+    type WebTransport;
+
+    #[wasm_bindgen(constructor, catch)]
+    fn new(url: &str, options: &Object) -> Result<WebTransport, JsValue>;
+
+    #[wasm_bindgen(method, getter)]
+    fn ready(this: &WebTransport) -> Promise;
+
+    #[wasm_bindgen(method, js_name = createBidirectionalStream)]
+    fn create_bidirectional_stream(this: &WebTransport) -> Promise;
+
+    #[wasm_bindgen(method)]
+    fn close(this: &WebTransport);
+
+    type BidirectionalStream;
+
+    #[wasm_bindgen(method, getter)]
+    fn readable(this: &BidirectionalStream) -> ReadableStream;
+
+    #[wasm_bindgen(method, getter)]
+    fn writable(this: &BidirectionalStream) -> WritableStream;
+
+    type ReadableStream;
+
+    #[wasm_bindgen(method, js_name = getReader)]
+    fn get_reader(this: &ReadableStream) -> ReadableStreamDefaultReader;
+
+    type ReadableStreamDefaultReader;
+
+    #[wasm_bindgen(method)]
+    fn read(this: &ReadableStreamDefaultReader) -> Promise;
+
+    type WritableStream;
+
+    #[wasm_bindgen(method, js_name = getWriter)]
+    fn get_writer(this: &WritableStream) -> WritableStreamDefaultWriter;
+
+    type WritableStreamDefaultWriter;
+
+    #[wasm_bindgen(method)]
+    fn write(this: &WritableStreamDefaultWriter, chunk: &Uint8Array) -> Promise;
+
+    type Response;
+
+    #[wasm_bindgen(method, getter)]
+    fn ok(this: &Response) -> bool;
+
+    #[wasm_bindgen(method, catch)]
+    fn json(this: &Response) -> Result<Promise, JsValue>;
+
+    // the service worker has no window, `fetch` is a global function
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch(url: &str) -> Promise;
+}
+
+/// Where the wallet serves WebTransport, as served on its `/webtransport` endpoint
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebTransportInfo {
+    port: u16,
+    /// Hex encoded SHA-256 hash of the self-signed certificate of the endpoint
+    certificate_hash: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct Error(String);
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WebTransport error: {}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        Self(format!("{value:?}"))
+    }
+}
+
+/// Sends the JSON-RPC messages on a bidirectional stream of the session
+pub(crate) struct Sender {
+    transport: WebTransport,
+    writer: WritableStreamDefaultWriter,
+}
+
+#[async_trait(?Send)]
+impl TransportSenderT for Sender {
+    type Error = Error;
+
+    async fn send(&mut self, msg: String) -> Result<(), Self::Error> {
+        JsFuture::from(self.writer.write(&Uint8Array::from(msg.as_bytes()))).await?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.transport.close();
+        Ok(())
+    }
+}
+
+/// Receives the JSON-RPC messages of the wallet, which aren't framed on the stream
+pub(crate) struct Receiver {
+    reader: ReadableStreamDefaultReader,
+    buf: Vec<u8>,
+    messages: VecDeque<String>,
+}
+
+#[async_trait(?Send)]
+impl TransportReceiverT for Receiver {
+    type Error = Error;
+
+    async fn receive(&mut self) -> Result<ReceivedMessage, Self::Error> {
+        loop {
+            if let Some(msg) = self.messages.pop_front() {
+                return Ok(ReceivedMessage::Text(msg));
+            }
+
+            let result = JsFuture::from(self.reader.read()).await?;
+            if Reflect::get(&result, &"done".into())?.is_truthy() {
+                return Err(Error("stream closed".to_string()));
+            }
+            let chunk = Uint8Array::new(&Reflect::get(&result, &"value".into())?);
+            self.buf.extend(chunk.to_vec());
+            self.take_messages()?;
+        }
+    }
+}
+
+impl Receiver {
+    /// Takes the complete JSON values out of the buffer, leaving an incomplete trailing one for
+    /// the next read
+    fn take_messages(&mut self) -> Result<(), Error> {
+        let mut values = serde_json::Deserializer::from_slice(&self.buf)
+            .into_iter::<&serde_json::value::RawValue>();
+        for value in values.by_ref() {
+            match value {
+                Ok(value) => self.messages.push_back(value.get().to_string()),
+                Err(err) if err.is_eof() => break,
+                Err(err) => return Err(Error(err.to_string())),
+            }
+        }
+        let consumed = values.byte_offset();
+        self.buf.drain(..consumed);
+        Ok(())
+    }
+}
+
+/// Opens a WebTransport session to the wallet at `host`, pinning the certificate advertised on
+/// its HTTP endpoint `info_url`, and a stream of JSON-RPC messages for the chain on it
+pub(crate) async fn connect(
+    info_url: &str,
+    host: &str,
+    chain: &str,
+) -> Result<(Sender, Receiver), Error> {
+    let response: Response = JsFuture::from(fetch(info_url)).await?.unchecked_into();
+    if !response.ok() {
+        return Err(Error("the wallet doesn't serve WebTransport".to_string()));
+    }
+    let info: WebTransportInfo =
+        serde_wasm_bindgen::from_value(JsFuture::from(response.json()?).await?)
+            .map_err(|err| Error(err.to_string()))?;
+
+    let transport = WebTransport::new(
+        &format!("https://{host}:{}/{chain}", info.port),
+        &options(&info.certificate_hash)?,
+    )?;
+    JsFuture::from(transport.ready()).await?;
+    let stream: BidirectionalStream = JsFuture::from(transport.create_bidirectional_stream())
+        .await?
+        .unchecked_into();
+
+    let writer = stream.writable().get_writer();
+    let reader = stream.readable().get_reader();
+    Ok((
+        Sender { transport, writer },
+        Receiver {
+            reader,
+            buf: Vec::new(),
+            messages: VecDeque::new(),
+        },
+    ))
+}
+
+/// Options trusting the self-signed certificate with the hash only
+fn options(certificate_hash: &str) -> Result<Object, Error> {
+    let hash = (0..certificate_hash.len())
+        .step_by(2)
+        .map(|i| {
+            certificate_hash
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| Error("invalid certificate hash".to_string()))?;
+
+    let pinned = Object::new();
+    Reflect::set(&pinned, &"algorithm".into(), &"sha-256".into())?;
+    Reflect::set(&pinned, &"value".into(), &Uint8Array::from(hash.as_slice()))?;
+    let options = Object::new();
+    Reflect::set(
+        &options,
+        &"serverCertificateHashes".into(),
+        &Array::of1(&pinned),
+    )?;
+    Ok(options)
+}
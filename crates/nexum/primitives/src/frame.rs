@@ -52,3 +52,13 @@ pub struct FrameState {
     pub available_chains: HashMap<Chain, ConnectionState>,
     pub current_chain_in_tab: Option<Chain>,
 }
+
+/// A chain served by the wallet, as returned by `nexum_chains`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainStatus {
+    pub chain_id: u64,
+    pub name: String,
+    /// Connected while any of the upstream rpcs of the chain is reachable
+    pub connected: ConnectionState,
+}
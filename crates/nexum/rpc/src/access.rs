@@ -0,0 +1,365 @@
+use hyper::{
+    Method, StatusCode,
+    header::{self, HeaderValue},
+};
+use jsonrpsee::server::{HttpBody, HttpRequest, HttpResponse, http::response};
+
+/// Hosts the server answers to by default, any other name pointing to the server is likely a DNS
+/// rebinding attempt
+pub const DEFAULT_ALLOWED_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "[::1]"];
+/// Origin of the wallet's Chrome extension, whose ID is pinned by the `key` of its manifest
+pub const EXTENSION_ORIGIN: &str = "chrome-extension://jifnbelekjggjlikcedbpjeomgnliajm";
+/// Browser origins allowed by default, i.e. the wallet's browser extension. Firefox gives the
+/// extension a random origin per install, which has to be allowed explicitly.
+pub const DEFAULT_ALLOWED_ORIGINS: [&str; 1] = [EXTENSION_ORIGIN];
+/// How long browsers may cache the result of a CORS preflight, in seconds
+const PREFLIGHT_MAX_AGE: &str = "600";
+
+/// Who may talk to the server.
+///
+/// The `Host` header must name an allowed host, which defeats DNS rebinding, and browsers, which
+/// always send the `Origin` of the page, must come from an allowed origin. Hosts and origins may
+/// contain `*` wildcards, e.g. `https://*.example.com`. Clients without an origin, like scripts,
+/// are allowed. When a token is set every request must carry it, either as an
+/// `Authorization: Bearer` header or, for WebSocket clients in browsers that can't set headers,
+/// as the `token` query parameter.
+///
+/// Browser extensions whose origin is only matched by a wildcard, like `chrome-extension://*`,
+/// must carry the token too, or else any installed extension could drive the wallet. They're
+/// rejected if there's no token, until their exact origin is allowed.
+#[derive(Clone, Debug)]
+pub struct AccessControl {
+    pub allowed_hosts: Vec<String>,
+    pub allowed_origins: Vec<String>,
+    pub token: Option<String>,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: DEFAULT_ALLOWED_HOSTS.map(String::from).to_vec(),
+            allowed_origins: DEFAULT_ALLOWED_ORIGINS.map(String::from).to_vec(),
+            token: None,
+        }
+    }
+}
+
+/// Why a request has been turned away
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    HostNotAllowed,
+    OriginNotAllowed,
+    Unauthorized,
+}
+
+impl Rejection {
+    pub fn into_response(self) -> HttpResponse {
+        match self {
+            Self::HostNotAllowed => response::host_not_allowed(),
+            Self::OriginNotAllowed => response::denied(),
+            Self::Unauthorized => HttpResponse::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(header::WWW_AUTHENTICATE, "Bearer")
+                .body(HttpBody::default())
+                .expect("unauthorized response is valid"),
+        }
+    }
+}
+
+impl AccessControl {
+    /// Validates the request, returning the origin of browser requests
+    pub fn check<B>(&self, req: &HttpRequest<B>) -> Result<Option<HeaderValue>, Rejection> {
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+            .map(strip_port);
+        if !host.is_some_and(|host| matches_any(&self.allowed_hosts, host)) {
+            tracing::warn!(?host, "rejected request to a host that isn't allowed");
+            return Err(Rejection::HostNotAllowed);
+        }
+
+        let origin = req.headers().get(header::ORIGIN);
+        if let Some(origin) = origin
+            && !origin
+                .to_str()
+                .is_ok_and(|origin| matches_any(&self.allowed_origins, origin))
+        {
+            tracing::warn!(
+                ?origin,
+                "rejected request from an origin that isn't allowed"
+            );
+            return Err(Rejection::OriginNotAllowed);
+        }
+
+        let wildcard_extension =
+            origin
+                .and_then(|origin| origin.to_str().ok())
+                .is_some_and(|origin| {
+                    is_extension_origin(origin)
+                        && !self.allowed_origins.iter().any(|allowed| allowed == origin)
+                });
+        // preflights never carry credentials
+        if req.method() != Method::OPTIONS
+            && (self.token.is_some() || wildcard_extension)
+            && !self
+                .token
+                .as_ref()
+                .is_some_and(|token| has_token(req, token))
+        {
+            if self.token.is_some() {
+                tracing::warn!("rejected request without a valid token");
+            } else {
+                tracing::warn!(
+                    ?origin,
+                    "rejected browser extension only matched by a wildcard origin, allow its exact \
+                     origin or set a token"
+                );
+            }
+            return Err(Rejection::Unauthorized);
+        }
+
+        Ok(origin.cloned())
+    }
+
+    /// Answers the CORS preflight of an allowed origin
+    pub fn preflight(origin: Option<&HeaderValue>) -> HttpResponse {
+        let mut rp = HttpResponse::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, "POST, OPTIONS")
+            .header(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                "authorization, content-type",
+            )
+            .header(header::ACCESS_CONTROL_MAX_AGE, PREFLIGHT_MAX_AGE)
+            .body(HttpBody::default())
+            .expect("preflight response is valid");
+        Self::allow_origin(&mut rp, origin);
+        rp
+    }
+
+    /// Lets the browser of an allowed origin read the response
+    pub fn allow_origin(rp: &mut HttpResponse, origin: Option<&HeaderValue>) {
+        if let Some(origin) = origin {
+            let headers = rp.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+            headers.insert(header::VARY, HeaderValue::from_static("origin"));
+        }
+    }
+}
+
+fn has_token<B>(req: &HttpRequest<B>, token: &str) -> bool {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|auth| auth.to_str().ok())
+        .and_then(|auth| auth.strip_prefix("Bearer "));
+    let query = req.uri().query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| value.into_owned())
+    });
+    bearer.is_some_and(|bearer| constant_time_eq(bearer, token))
+        || query.is_some_and(|query| constant_time_eq(&query, token))
+}
+
+/// Returns whether the origin is the one of a browser extension, which any installed extension
+/// may claim if it's allowed by a wildcard
+fn is_extension_origin(origin: &str) -> bool {
+    origin.starts_with("chrome-extension://") || origin.starts_with("moz-extension://")
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Removes the port from a `Host` header, keeping the brackets of IPv6 addresses
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+            if name.starts_with('[') || !name.contains(':') {
+                name
+            } else {
+                host
+            }
+        }
+        _ => host,
+    }
+}
+
+fn matches_any(patterns: &[String], value: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern, value))
+}
+
+/// Matches the value against the pattern, in which `*` stands for any sequence of characters
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: &str, origin: Option<&str>, token: Option<&str>) -> HttpRequest<()> {
+        let mut req = HttpRequest::builder()
+            .method(Method::POST)
+            .uri("/mainnet")
+            .header(header::HOST, host);
+        if let Some(origin) = origin {
+            req = req.header(header::ORIGIN, origin);
+        }
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("localhost", "localhost"));
+        assert!(!wildcard_match("localhost", "localhost.evil.com"));
+        assert!(wildcard_match(
+            "https://*.example.com",
+            "https://app.example.com"
+        ));
+        assert!(wildcard_match(
+            "https://*.example.com",
+            "https://a.b.example.com"
+        ));
+        assert!(!wildcard_match(
+            "https://*.example.com",
+            "https://example.com"
+        ));
+        assert!(!wildcard_match(
+            "https://*.example.com",
+            "https://example.com.evil.com"
+        ));
+        assert!(wildcard_match(
+            "chrome-extension://*",
+            "chrome-extension://abcdef"
+        ));
+        assert!(!wildcard_match(
+            "chrome-extension://*",
+            "moz-extension://abcdef"
+        ));
+        assert!(wildcard_match("http://*:*", "http://localhost:3000"));
+        assert!(!wildcard_match("a*b*c", "abcb"));
+    }
+
+    #[test]
+    fn strips_ports() {
+        assert_eq!(strip_port("localhost:1250"), "localhost");
+        assert_eq!(strip_port("localhost"), "localhost");
+        assert_eq!(strip_port("[::1]:1250"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("::1"), "::1");
+    }
+
+    #[test]
+    fn checks_hosts_and_origins() {
+        let access = AccessControl {
+            allowed_origins: vec!["https://*.example.com".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            access.check(&request("127.0.0.1:1250", None, None)),
+            Ok(None)
+        );
+        assert_eq!(
+            access.check(&request("evil.com:1250", None, None)),
+            Err(Rejection::HostNotAllowed)
+        );
+        assert!(
+            access
+                .check(&request("localhost", Some("https://app.example.com"), None))
+                .is_ok_and(|origin| origin.is_some())
+        );
+        assert_eq!(
+            access.check(&request("localhost", Some("https://evil.com"), None)),
+            Err(Rejection::OriginNotAllowed)
+        );
+    }
+
+    #[test]
+    fn allows_the_wallet_extension_by_default() {
+        let access = AccessControl::default();
+        assert!(
+            access
+                .check(&request("127.0.0.1:1250", Some(EXTENSION_ORIGIN), None))
+                .is_ok_and(|origin| origin.is_some())
+        );
+        assert_eq!(
+            access.check(&request(
+                "127.0.0.1:1250",
+                Some("chrome-extension://abcdef"),
+                None
+            )),
+            Err(Rejection::OriginNotAllowed)
+        );
+    }
+
+    #[test]
+    fn wildcard_extensions_need_the_token() {
+        let extension = Some("chrome-extension://abcdef");
+        let access = AccessControl {
+            allowed_origins: vec!["chrome-extension://*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            access.check(&request("localhost", extension, None)),
+            Err(Rejection::Unauthorized)
+        );
+
+        let access = AccessControl {
+            allowed_origins: vec!["chrome-extension://*".to_string()],
+            token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            access.check(&request("localhost", extension, Some("wrong"))),
+            Err(Rejection::Unauthorized)
+        );
+        assert!(
+            access
+                .check(&request("localhost", extension, Some("secret")))
+                .is_ok()
+        );
+
+        let access = AccessControl {
+            allowed_origins: vec!["chrome-extension://abcdef".to_string()],
+            ..Default::default()
+        };
+        assert!(access.check(&request("localhost", extension, None)).is_ok());
+        assert_eq!(
+            access.check(&request(
+                "localhost",
+                Some("chrome-extension://other"),
+                None
+            )),
+            Err(Rejection::OriginNotAllowed)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::net::Ipv4Addr;
+use std::{net::IpAddr, path::PathBuf};
 
 use alloy_chains::NamedChain;
 use clap::Parser;
@@ -9,15 +9,33 @@ use url::Url;
 #[derive(Parser, Debug)]
 #[command(about)]
 pub struct Args {
-    #[arg(short = 'H', long, default_value = "127.0.0.1")]
-    pub host: Ipv4Addr,
+    /// Addresses to listen on, repeat to listen on several, e.g. `-H 127.0.0.1 -H ::1`
+    #[arg(short = 'H', long = "host", default_value = "127.0.0.1")]
+    pub hosts: Vec<IpAddr>,
 
     #[arg(short, long, default_value = "1248")]
     pub port: u16,
 
-    /// Node JSON-RPC URLs in the format `<chain>=<url>`
+    /// Node JSON-RPC URLs in the format `<chain>=<url>`, repeat a chain to add fallback
+    /// upstreams
     #[arg(short, long)]
     pub rpc_urls: Vec<String>,
+
+    /// Also listen for raw JSON-RPC on a Unix domain socket at this path
+    #[arg(long)]
+    pub ipc_path: Option<PathBuf>,
+
+    /// Also serve JSON-RPC over WebTransport on this UDP port, with a self-signed certificate
+    #[arg(long)]
+    pub webtransport_port: Option<u16>,
+
+    /// Browser origins allowed to connect, e.g. `https://app.example.com`
+    #[arg(long = "allowed-origin")]
+    pub allowed_origins: Vec<String>,
+
+    /// Bearer token the clients must authenticate with
+    #[arg(long)]
+    pub token: Option<String>,
 }
 
 #[tokio::main]
@@ -25,7 +43,19 @@ async fn main() -> eyre::Result<()> {
     let args = Args::parse();
     tracing_subscriber::fmt::init();
 
-    let mut builder = RpcServerBuilder::new().host(args.host).port(args.port);
+    let mut builder = RpcServerBuilder::new().hosts(args.hosts).port(args.port);
+    if let Some(ipc_path) = args.ipc_path {
+        builder = builder.ipc_path(ipc_path);
+    }
+    if let Some(port) = args.webtransport_port {
+        builder = builder.webtransport_port(port);
+    }
+    if !args.allowed_origins.is_empty() {
+        builder = builder.allowed_origins(args.allowed_origins);
+    }
+    if let Some(token) = args.token {
+        builder = builder.token(token);
+    }
 
     for (chain, url) in args
         .rpc_urls
@@ -0,0 +1,367 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use futures::{
+    FutureExt,
+    future::{BoxFuture, Shared},
+};
+use serde::Serialize;
+
+use crate::{error::WalletError, rpc::RequestParams};
+
+/// Maximum number of results cached per chain
+const MAX_ENTRIES: usize = 4096;
+/// Maximum size, in bytes of serialized JSON, of the results cached per chain
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// Read-only upstream methods whose results may be coalesced. Any other request, e.g. one creating
+/// or consuming state on the node like filters or transactions, reaches it once per call.
+const COALESCED_METHODS: [&str; 26] = [
+    "web3_clientVersion",
+    "net_version",
+    "eth_syncing",
+    "eth_chainId",
+    "eth_gasPrice",
+    "eth_blockNumber",
+    "eth_getBalance",
+    "eth_getStorageAt",
+    "eth_getTransactionCount",
+    "eth_getBlockTransactionCountByHash",
+    "eth_getBlockTransactionCountByNumber",
+    "eth_getUncleCountByBlockHash",
+    "eth_getUncleCountByBlockNumber",
+    "eth_getCode",
+    "eth_call",
+    "eth_estimateGas",
+    "eth_getBlockByHash",
+    "eth_getBlockByNumber",
+    "eth_getTransactionByHash",
+    "eth_getTransactionByBlockHashAndIndex",
+    "eth_getTransactionByBlockNumberAndIndex",
+    "eth_getTransactionReceipt",
+    "eth_getUncleByBlockHashAndIndex",
+    "eth_getUncleByBlockNumberAndIndex",
+    "eth_getLogs",
+    "eth_feeHistory",
+];
+
+type Key = (&'static str, String);
+type Response = Result<serde_json::Value, WalletError>;
+type InFlight = HashMap<Key, Shared<BoxFuture<'static, Response>>>;
+
+/// Cache of the upstream results of a chain that can't change anymore, e.g. the chain id, blocks
+/// by hash or the receipts of mined transactions. Identical requests that are in flight at the
+/// same time share a single upstream request, cached or not.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseCache(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: RwLock<Entries>,
+    in_flight: RwLock<InFlight>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// The cached results, evicted oldest first once the limits are reached
+#[derive(Debug, Default)]
+struct Entries {
+    results: HashMap<Key, (serde_json::Value, usize)>,
+    order: VecDeque<Key>,
+    bytes: usize,
+}
+
+/// Counters of a [`ResponseCache`]
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    /// Requests answered from the cache
+    pub hits: u64,
+    /// Requests for cacheable results that went upstream
+    pub misses: u64,
+    /// Requests that joined an identical in-flight request
+    pub coalesced: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl ResponseCache {
+    /// Answers the request from the cache, or from the in-flight identical request, or with
+    /// `fetch`, caching the result if it's final
+    pub async fn request<Fut>(
+        &self,
+        method: &'static str,
+        params: &RequestParams,
+        fetch: impl FnOnce() -> Fut,
+    ) -> Response
+    where
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        if !COALESCED_METHODS.contains(&method) {
+            return fetch().await;
+        }
+
+        let key = (method, serde_json::Value::from(params.clone()).to_string());
+        let cacheable = is_cacheable(method, params);
+        if cacheable {
+            if let Some(result) = self.get(&key) {
+                self.0.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(result);
+            }
+            self.0.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let response = {
+            let mut in_flight = self.w_in_flight();
+            match in_flight.get(&key) {
+                Some(response) => {
+                    self.0.coalesced.fetch_add(1, Ordering::Relaxed);
+                    response.clone()
+                }
+                None => {
+                    let this = self.clone();
+                    let fetch = fetch();
+                    let response = {
+                        let key = key.clone();
+                        async move {
+                            let response = fetch.await;
+                            this.w_in_flight().remove(&key);
+                            if let Ok(result) = &response
+                                && cacheable
+                                && is_final(method, result)
+                            {
+                                this.insert(key, result.clone());
+                            }
+                            response
+                        }
+                    }
+                    .boxed()
+                    .shared();
+                    in_flight.insert(key, response.clone());
+                    response
+                }
+            }
+        };
+        response.await
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.r_entries();
+        CacheStats {
+            hits: self.0.hits.load(Ordering::Relaxed),
+            misses: self.0.misses.load(Ordering::Relaxed),
+            coalesced: self.0.coalesced.load(Ordering::Relaxed),
+            entries: entries.results.len(),
+            bytes: entries.bytes,
+        }
+    }
+
+    fn get(&self, key: &Key) -> Option<serde_json::Value> {
+        let entries = self.r_entries();
+        entries.results.get(key).map(|(result, _)| result.clone())
+    }
+
+    fn insert(&self, key: Key, result: serde_json::Value) {
+        let size = key.1.len() + result.to_string().len();
+        if size > MAX_BYTES {
+            return;
+        }
+
+        let mut entries = self.w_entries();
+        if entries.results.contains_key(&key) {
+            return;
+        }
+        while entries.results.len() >= MAX_ENTRIES || entries.bytes + size > MAX_BYTES {
+            let Some(oldest) = entries.order.pop_front() else {
+                break;
+            };
+            if let Some((_, size)) = entries.results.remove(&oldest) {
+                entries.bytes -= size;
+            }
+        }
+        entries.bytes += size;
+        entries.order.push_back(key.clone());
+        entries.results.insert(key, (result, size));
+    }
+
+    fn r_entries(&self) -> RwLockReadGuard<'_, Entries> {
+        self.0
+            .entries
+            .read()
+            .expect("failed to get read lock on cache entries")
+    }
+
+    fn w_entries(&self) -> RwLockWriteGuard<'_, Entries> {
+        self.0
+            .entries
+            .write()
+            .expect("failed to get write lock on cache entries")
+    }
+
+    fn w_in_flight(&self) -> RwLockWriteGuard<'_, InFlight> {
+        self.0
+            .in_flight
+            .write()
+            .expect("failed to get write lock on in-flight requests")
+    }
+}
+
+/// Returns whether the result of the request may be cached, before knowing it
+fn is_cacheable(method: &str, params: &RequestParams) -> bool {
+    match method {
+        "eth_chainId"
+        | "net_version"
+        | "eth_getBlockByHash"
+        | "eth_getTransactionByHash"
+        | "eth_getTransactionReceipt" => true,
+        // the code only can't change at a block given by its hash, see EIP-1898. The code at a
+        // finalized block number can't either, but isn't cached as telling would take a request
+        "eth_getCode" => match params {
+            RequestParams::Array(params) => params.get(1).is_some_and(|block| {
+                block.get("blockHash").is_some()
+                    || block.as_str().is_some_and(|block| block.len() == 66)
+            }),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns whether the result can't change anymore, e.g. the transaction has been mined
+fn is_final(method: &str, result: &serde_json::Value) -> bool {
+    match method {
+        "eth_getBlockByHash" => !result.is_null(),
+        "eth_getTransactionByHash" | "eth_getTransactionReceipt" => result
+            .get("blockHash")
+            .is_some_and(|block_hash| !block_hash.is_null()),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key(idx: usize) -> Key {
+        ("eth_getBlockByHash", format!("[\"{idx:#066x}\",false]"))
+    }
+
+    #[test]
+    fn evicts_oldest_entries_first() {
+        let cache = ResponseCache::default();
+        for idx in 0..=MAX_ENTRIES {
+            cache.insert(key(idx), json!(idx));
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, MAX_ENTRIES);
+        assert_eq!(cache.get(&key(0)), None);
+        assert_eq!(cache.get(&key(1)), Some(json!(1)));
+        assert_eq!(cache.get(&key(MAX_ENTRIES)), Some(json!(MAX_ENTRIES)));
+    }
+
+    #[test]
+    fn evicts_entries_over_the_size_limit() {
+        let cache = ResponseCache::default();
+        let large = json!("a".repeat(MAX_BYTES / 2));
+        cache.insert(key(0), large.clone());
+        cache.insert(key(1), large.clone());
+        assert_eq!(cache.get(&key(0)), None);
+        assert_eq!(cache.get(&key(1)), Some(large));
+
+        // a result that can't fit at all isn't cached, nor evicts anything
+        cache.insert(key(2), json!("a".repeat(MAX_BYTES)));
+        assert_eq!(cache.get(&key(2)), None);
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[test]
+    fn pending_results_are_not_final() {
+        assert!(!is_final("eth_getBlockByHash", &serde_json::Value::Null));
+        assert!(is_final("eth_getBlockByHash", &json!({ "number": "0x1" })));
+        assert!(!is_final(
+            "eth_getTransactionByHash",
+            &json!({ "blockHash": null })
+        ));
+        assert!(!is_final(
+            "eth_getTransactionReceipt",
+            &serde_json::Value::Null
+        ));
+        assert!(is_final(
+            "eth_getTransactionReceipt",
+            &json!({ "blockHash": format!("{:#066x}", 1) })
+        ));
+        assert!(is_final("eth_chainId", &json!("0x1")));
+    }
+
+    #[test]
+    fn caches_code_at_a_block_hash_only() {
+        let code_at = |block| RequestParams::Array(vec![json!(format!("{:#042x}", 1)), block]);
+        assert!(is_cacheable(
+            "eth_getCode",
+            &code_at(json!(format!("{:#066x}", 1)))
+        ));
+        assert!(is_cacheable(
+            "eth_getCode",
+            &code_at(json!({ "blockHash": format!("{:#066x}", 1) }))
+        ));
+        assert!(!is_cacheable("eth_getCode", &code_at(json!("0x1"))));
+        assert!(!is_cacheable("eth_getCode", &code_at(json!("finalized"))));
+    }
+
+    #[tokio::test]
+    async fn does_not_coalesce_other_methods() {
+        let cache = ResponseCache::default();
+        let params = RequestParams::Array(vec![]);
+        let fetches = Arc::new(AtomicU64::new(0));
+        let fetch = || {
+            let fetches = fetches.clone();
+            async move {
+                tokio::task::yield_now().await;
+                Ok(json!(fetches.fetch_add(1, Ordering::Relaxed)))
+            }
+        };
+        let (first, second) = futures::join!(
+            cache.request("eth_newBlockFilter", &params, fetch),
+            cache.request("eth_newBlockFilter", &params, fetch)
+        );
+        assert_ne!(first.unwrap(), second.unwrap());
+        assert_eq!(cache.stats().coalesced, 0);
+    }
+
+    #[tokio::test]
+    async fn caches_final_results_only() {
+        let cache = ResponseCache::default();
+        let params = RequestParams::Array(vec![json!(format!("{:#066x}", 1))]);
+        let pending = json!({ "blockHash": null });
+        let result = cache
+            .request("eth_getTransactionReceipt", &params, || {
+                let pending = pending.clone();
+                async move { Ok(pending) }
+            })
+            .await;
+        assert_eq!(result.unwrap(), pending);
+        assert_eq!(cache.stats().entries, 0);
+
+        let mined = json!({ "blockHash": format!("{:#066x}", 2) });
+        for _ in 0..2 {
+            let result = cache
+                .request("eth_getTransactionReceipt", &params, || {
+                    let mined = mined.clone();
+                    async move { Ok(mined) }
+                })
+                .await;
+            assert_eq!(result.unwrap(), mined);
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 2));
+    }
+}
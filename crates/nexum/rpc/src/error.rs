@@ -0,0 +1,159 @@
+use alloy::{
+    primitives::U64,
+    transports::{RpcError, TransportError},
+};
+use alloy_chains::NamedChain;
+use jsonrpsee::types::{ErrorCode, ErrorObject, ErrorObjectOwned};
+use serde_json::value::RawValue;
+
+/// Errors returned to the clients. Each maps to an EIP-1193 provider error, an EIP-1474
+/// JSON-RPC error or an EIP-5792 error, so dapps can react to them.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum WalletError {
+    #[error("user rejected the request")]
+    UserRejected,
+    #[error("the requested account has not been authorized")]
+    Unauthorized,
+    #[error("unsupported method {0}")]
+    UnsupportedMethod(String),
+    /// The wallet can't serve any request, e.g. no account is active or the UI is gone
+    #[error("the wallet is disconnected")]
+    Disconnected,
+    /// The upstream of the chain can't be reached
+    #[error("disconnected from chain {0}")]
+    ChainDisconnected(U64),
+    /// Error returned by the upstream node, passed through as is so the revert data of calls
+    /// reaches the dapp
+    #[error("{message}")]
+    Upstream {
+        code: i32,
+        message: String,
+        data: Option<Box<RawValue>>,
+    },
+    /// EIP-3326, the chain isn't configured
+    #[error("unrecognized chain id {0}")]
+    UnrecognizedChain(U64),
+    #[error("unsupported capability {0}")]
+    UnsupportedCapability(String),
+    #[error("chain id {0} is not supported")]
+    UnsupportedChainId(U64),
+    #[error("duplicate batch id {0}")]
+    DuplicateBatchId(String),
+    #[error("unknown batch id {0}")]
+    UnknownBatchId(String),
+    #[error("atomic execution is not supported")]
+    AtomicityNotSupported,
+    #[error("{0}")]
+    InvalidParams(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl WalletError {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::UserRejected => 4001,
+            Self::Unauthorized => 4100,
+            Self::UnsupportedMethod(_) => 4200,
+            Self::Disconnected => 4900,
+            Self::ChainDisconnected(_) => 4901,
+            Self::Upstream { code, .. } => *code,
+            Self::UnrecognizedChain(_) => 4902,
+            Self::UnsupportedCapability(_) => 5700,
+            Self::UnsupportedChainId(_) => 5710,
+            Self::DuplicateBatchId(_) => 5720,
+            Self::UnknownBatchId(_) => 5730,
+            Self::AtomicityNotSupported => 5760,
+            Self::InvalidParams(_) => ErrorCode::InvalidParams.code(),
+            Self::Internal(_) => ErrorCode::InternalError.code(),
+        }
+    }
+
+    pub fn internal<E>(err: E) -> Self
+    where
+        E: std::fmt::Debug,
+    {
+        Self::Internal(format!("{err:?}"))
+    }
+
+    /// Maps the error of a request to the upstream of the chain. Node-side errors are passed
+    /// through, while failing to reach the node means the chain is disconnected.
+    pub fn upstream(chain: NamedChain, err: TransportError) -> Self {
+        match err {
+            RpcError::ErrorResp(payload) => match i32::try_from(payload.code) {
+                Ok(code) => Self::Upstream {
+                    code,
+                    message: payload.message.into_owned(),
+                    data: payload.data,
+                },
+                // JSON-RPC error codes are 32-bit, keep the original one in the data
+                Err(_) => Self::Upstream {
+                    code: ErrorCode::InternalError.code(),
+                    message: payload.message.into_owned(),
+                    data: serde_json::value::to_raw_value(&serde_json::json!({
+                        "code": payload.code,
+                        "data": payload.data,
+                    }))
+                    .ok(),
+                },
+            },
+            RpcError::Transport(e) => {
+                tracing::warn!(?e, %chain, "upstream request failed");
+                Self::ChainDisconnected(U64::from(chain as u64))
+            }
+            e => Self::internal(e),
+        }
+    }
+
+    /// Error for an interactive response that doesn't match the request
+    pub fn unexpected_response() -> Self {
+        Self::Internal("unexpected interactive response".to_string())
+    }
+}
+
+impl From<WalletError> for ErrorObjectOwned {
+    fn from(err: WalletError) -> Self {
+        match err {
+            WalletError::Upstream {
+                code,
+                message,
+                data,
+            } => ErrorObject::owned(code, message, data),
+            err => ErrorObject::owned(err.code(), err.to_string(), None::<()>),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::json_rpc::ErrorPayload;
+
+    use super::*;
+
+    fn upstream_error(code: i64) -> WalletError {
+        let payload = ErrorPayload {
+            code,
+            message: "execution reverted".into(),
+            data: serde_json::value::to_raw_value("0x01").ok(),
+        };
+        WalletError::upstream(NamedChain::Mainnet, RpcError::ErrorResp(payload))
+    }
+
+    #[test]
+    fn passes_upstream_errors_through() {
+        let err = ErrorObjectOwned::from(upstream_error(3));
+        assert_eq!(err.code(), 3);
+        assert_eq!(err.message(), "execution reverted");
+        assert_eq!(err.data().map(|data| data.get()), Some("\"0x01\""));
+    }
+
+    #[test]
+    fn maps_out_of_range_upstream_codes() {
+        let err = ErrorObjectOwned::from(upstream_error(i64::from(i32::MAX) + 1));
+        assert_eq!(err.code(), ErrorCode::InternalError.code());
+        assert_eq!(
+            err.data().map(|data| data.get()),
+            Some(r#"{"code":2147483648,"data":"0x01"}"#)
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::Ethereum,
+    providers::{
+        Provider, SendableTx,
+        fillers::{FillerControlFlow, TxFiller},
+    },
+    rpc::types::TransactionRequest,
+    transports::TransportResult,
+};
+use alloy_chains::NamedChain;
+use serde::{Deserialize, Serialize};
+
+/// Number of blocks the priority fees are sampled from
+const FEE_HISTORY_BLOCKS: u64 = 10;
+
+/// How fast a transaction should be included, the percentile of the priority fees paid in the
+/// last blocks it bids
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl FeeSpeed {
+    pub const fn percentile(self) -> f64 {
+        match self {
+            Self::Slow => 10.0,
+            Self::Normal => 50.0,
+            Self::Fast => 90.0,
+        }
+    }
+}
+
+/// How the fees of the transactions of a chain are priced, fees set by the client are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeStrategy {
+    pub speed: FeeSpeed,
+    /// Prices the transactions with the `eth_gasPrice` of the node, for chains without EIP-1559
+    pub legacy: bool,
+    /// Highest max fee per gas, or gas price of legacy transactions, in wei
+    pub max_fee: Option<u64>,
+    /// Lowest priority fee per gas, in wei
+    pub min_priority_fee: Option<u64>,
+}
+
+impl FeeStrategy {
+    /// Max fee and priority fee per gas bidding the speed's percentile of the priority fees paid
+    /// in the last blocks on top of twice the next base fee. `None` if the chain has no base fee.
+    async fn eip1559_fees<P: Provider>(
+        &self,
+        provider: &P,
+    ) -> TransportResult<Option<(u128, u128)>> {
+        let history = provider
+            .get_fee_history(
+                FEE_HISTORY_BLOCKS,
+                BlockNumberOrTag::Latest,
+                &[self.speed.percentile()],
+            )
+            .await?;
+        let Some(base_fee) = history.next_block_base_fee().filter(|fee| *fee != 0) else {
+            return Ok(None);
+        };
+        // empty blocks pay no priority fees, they don't tell what it takes to be included
+        let mut rewards = history
+            .reward
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|rewards| rewards.first().copied())
+            .filter(|reward| *reward != 0)
+            .collect::<Vec<_>>();
+        rewards.sort_unstable();
+        let reward = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+        let priority_fee = reward.max(self.min_priority_fee.unwrap_or_default().into());
+        let max_fee = self.cap(base_fee.saturating_mul(2).saturating_add(priority_fee));
+        Ok(Some((max_fee, priority_fee.min(max_fee))))
+    }
+
+    fn cap(&self, fee: u128) -> u128 {
+        self.max_fee.map_or(fee, |max_fee| fee.min(max_fee.into()))
+    }
+}
+
+/// Fee strategy of each chain. The handle is shared by the fillers of the chains, so a strategy
+/// changed while the server runs prices the next transactions.
+#[derive(Clone, Debug, Default)]
+pub struct FeeStrategies(Arc<RwLock<HashMap<NamedChain, FeeStrategy>>>);
+
+impl FeeStrategies {
+    /// Strategy of the chain, the default one if it has none
+    pub fn get(&self, chain: NamedChain) -> FeeStrategy {
+        self.0
+            .read()
+            .expect("failed to get read lock on fee strategies")
+            .get(&chain)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Replaces the strategies of every chain
+    pub fn sync(&self, strategies: HashMap<NamedChain, FeeStrategy>) {
+        *self
+            .0
+            .write()
+            .expect("failed to get write lock on fee strategies") = strategies;
+    }
+}
+
+/// Fields filled by the [`FeeFiller`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fees {
+    Legacy {
+        gas_limit: u64,
+        gas_price: u128,
+    },
+    Eip1559 {
+        gas_limit: u64,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    },
+}
+
+/// Fills the gas limit and the fees of transactions with the fee strategy of the chain, in place
+/// of alloy's `GasFiller`. Like it, it falls back to legacy pricing on chains without a base fee.
+#[derive(Clone, Debug)]
+pub struct FeeFiller {
+    chain: NamedChain,
+    strategies: FeeStrategies,
+}
+
+impl FeeFiller {
+    pub fn new(chain: NamedChain, strategies: FeeStrategies) -> Self {
+        Self { chain, strategies }
+    }
+
+    async fn prepare_legacy<P: Provider>(
+        &self,
+        provider: &P,
+        tx: &TransactionRequest,
+        strategy: FeeStrategy,
+    ) -> TransportResult<Fees> {
+        let gas_limit = gas_limit(provider, tx).await?;
+        let gas_price = match tx.gas_price {
+            Some(gas_price) => gas_price,
+            None => strategy.cap(provider.get_gas_price().await?),
+        };
+        Ok(Fees::Legacy {
+            gas_limit,
+            gas_price,
+        })
+    }
+}
+
+async fn gas_limit<P: Provider>(provider: &P, tx: &TransactionRequest) -> TransportResult<u64> {
+    match tx.gas {
+        Some(gas_limit) => Ok(gas_limit),
+        None => provider.estimate_gas(tx.clone()).await,
+    }
+}
+
+impl TxFiller for FeeFiller {
+    type Fillable = Fees;
+
+    fn status(&self, tx: &TransactionRequest) -> FillerControlFlow {
+        let priced = tx.gas_price.is_some()
+            || (tx.max_fee_per_gas.is_some() && tx.max_priority_fee_per_gas.is_some());
+        if priced && tx.gas.is_some() {
+            FillerControlFlow::Finished
+        } else {
+            FillerControlFlow::Ready
+        }
+    }
+
+    fn fill_sync(&self, _tx: &mut SendableTx<Ethereum>) {}
+
+    async fn prepare<P: Provider>(
+        &self,
+        provider: &P,
+        tx: &TransactionRequest,
+    ) -> TransportResult<Self::Fillable> {
+        let strategy = self.strategies.get(self.chain);
+        let eip1559 = tx.max_fee_per_gas.is_some() || tx.max_priority_fee_per_gas.is_some();
+        if tx.gas_price.is_some() || (strategy.legacy && !eip1559) {
+            return self.prepare_legacy(provider, tx, strategy).await;
+        }
+        let (max_fee_per_gas, max_priority_fee_per_gas) =
+            match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+                (Some(max_fee), Some(priority_fee)) => (max_fee, priority_fee),
+                (max_fee, priority_fee) => match strategy.eip1559_fees(provider).await? {
+                    Some((estimated_max_fee, estimated_priority_fee)) => {
+                        let max_fee = max_fee.unwrap_or(estimated_max_fee);
+                        (
+                            max_fee,
+                            priority_fee.unwrap_or(estimated_priority_fee.min(max_fee)),
+                        )
+                    }
+                    // without a base fee, the fee the client set is all there is to pay
+                    None if eip1559 => {
+                        let fee = max_fee.or(priority_fee).unwrap_or_default();
+                        (max_fee.unwrap_or(fee), priority_fee.unwrap_or(fee))
+                    }
+                    None => return self.prepare_legacy(provider, tx, strategy).await,
+                },
+            };
+        Ok(Fees::Eip1559 {
+            gas_limit: gas_limit(provider, tx).await?,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        })
+    }
+
+    async fn fill(
+        &self,
+        fillable: Self::Fillable,
+        mut tx: SendableTx<Ethereum>,
+    ) -> TransportResult<SendableTx<Ethereum>> {
+        if let Some(builder) = tx.as_mut_builder() {
+            match fillable {
+                Fees::Legacy {
+                    gas_limit,
+                    gas_price,
+                } => {
+                    builder.gas = Some(gas_limit);
+                    builder.gas_price = Some(gas_price);
+                }
+                Fees::Eip1559 {
+                    gas_limit,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                } => {
+                    builder.gas = Some(gas_limit);
+                    builder.max_fee_per_gas = Some(max_fee_per_gas);
+                    builder.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+                }
+            }
+        }
+        Ok(tx)
+    }
+}
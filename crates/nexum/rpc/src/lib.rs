@@ -1,2 +1,13 @@
+pub mod access;
+pub mod cache;
+pub mod error;
+pub mod fees;
+pub mod metrics;
 pub mod namespaces;
+pub mod nonces;
 pub mod rpc;
+pub mod simulation;
+pub mod status;
+pub mod subscriptions;
+pub mod upstream;
+pub mod webtransport;
@@ -0,0 +1,299 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use alloy_chains::NamedChain;
+
+use crate::cache::CacheStats;
+
+/// Upper bounds, in seconds, of the buckets of the call latencies
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Upper bounds, in seconds, of the buckets of the time users take to answer prompts
+const DECISION_BUCKETS: [f64; 9] = [1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Server metrics, rendered in the Prometheus text format on `/metrics`
+#[derive(Clone, Debug, Default)]
+pub struct Metrics(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    opened_ws_connections: AtomicU64,
+    closed_ws_connections: AtomicU64,
+    http_calls: AtomicU64,
+    success_http_calls: AtomicU64,
+    pending_prompts: AtomicU64,
+    /// Calls by chain and method
+    calls: Mutex<BTreeMap<(String, String), Calls>>,
+    /// Time users took to answer prompts, by method
+    decisions: Mutex<BTreeMap<String, Histogram<{ DECISION_BUCKETS.len() }>>>,
+}
+
+impl Inner {
+    fn lock_calls(&self) -> MutexGuard<'_, BTreeMap<(String, String), Calls>> {
+        self.calls.lock().expect("failed to lock call metrics")
+    }
+
+    fn lock_decisions(
+        &self,
+    ) -> MutexGuard<'_, BTreeMap<String, Histogram<{ DECISION_BUCKETS.len() }>>> {
+        self.decisions
+            .lock()
+            .expect("failed to lock prompt decision metrics")
+    }
+}
+
+#[derive(Debug, Default)]
+struct Calls {
+    errors: u64,
+    latency: Histogram<{ LATENCY_BUCKETS.len() }>,
+}
+
+/// Cumulative histogram, the last bucket is `+Inf`
+#[derive(Debug)]
+struct Histogram<const N: usize> {
+    buckets: [u64; N],
+    sum: f64,
+    count: u64,
+}
+
+impl<const N: usize> Default for Histogram<N> {
+    fn default() -> Self {
+        Self {
+            buckets: [0; N],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl<const N: usize> Histogram<N> {
+    fn observe(&mut self, bounds: &[f64; N], value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(bounds) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str, bounds: &[f64; N]) {
+        for (bucket, bound) in self.buckets.iter().zip(bounds) {
+            _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {bucket}");
+        }
+        _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+/// A prompt shown to the user, recorded as decided when dropped
+pub struct PendingPrompt {
+    metrics: Metrics,
+    method: String,
+    started: Instant,
+}
+
+impl Drop for PendingPrompt {
+    fn drop(&mut self) {
+        self.metrics
+            .0
+            .pending_prompts
+            .fetch_sub(1, Ordering::Relaxed);
+        self.metrics
+            .0
+            .lock_decisions()
+            .entry(std::mem::take(&mut self.method))
+            .or_default()
+            .observe(&DECISION_BUCKETS, self.started.elapsed().as_secs_f64());
+    }
+}
+
+impl Metrics {
+    pub fn ws_opened(&self) {
+        self.0.opened_ws_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn ws_closed(&self) {
+        self.0.closed_ws_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn http_call(&self) {
+        self.0.http_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn http_call_succeeded(&self) {
+        self.0.success_http_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a JSON-RPC call served on the chain
+    pub fn call(&self, chain: Option<NamedChain>, method: &str, latency: Duration, error: bool) {
+        let chain = chain.map_or_else(|| "unknown".to_string(), |chain| chain.to_string());
+        let mut calls = self.0.lock_calls();
+        let calls = calls.entry((chain, method.to_string())).or_default();
+        calls.errors += error as u64;
+        calls
+            .latency
+            .observe(&LATENCY_BUCKETS, latency.as_secs_f64());
+    }
+
+    /// Starts timing the decision of the user on a prompt
+    pub fn prompt(&self, method: &str) -> PendingPrompt {
+        self.0.pending_prompts.fetch_add(1, Ordering::Relaxed);
+        PendingPrompt {
+            metrics: self.clone(),
+            method: method.to_string(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Renders the metrics, along with the counters of the response caches, in the Prometheus
+    /// text format
+    pub fn render(&self, caches: &HashMap<NamedChain, CacheStats>) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "nexum_ws_connections_opened_total",
+                "WebSocket connections opened",
+                &self.0.opened_ws_connections,
+            ),
+            (
+                "nexum_ws_connections_closed_total",
+                "WebSocket connections closed",
+                &self.0.closed_ws_connections,
+            ),
+            (
+                "nexum_http_requests_total",
+                "HTTP requests received",
+                &self.0.http_calls,
+            ),
+            (
+                "nexum_http_requests_succeeded_total",
+                "HTTP requests answered successfully",
+                &self.0.success_http_calls,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            header(&mut out, name, help, "counter");
+            _ = writeln!(out, "{name} {}", counter.load(Ordering::Relaxed));
+        }
+
+        header(
+            &mut out,
+            "nexum_prompts_pending",
+            "Calls waiting on the decision of the user",
+            "gauge",
+        );
+        _ = writeln!(
+            out,
+            "nexum_prompts_pending {}",
+            self.0.pending_prompts.load(Ordering::Relaxed)
+        );
+
+        let calls = self.0.lock_calls();
+        header(
+            &mut out,
+            "nexum_rpc_requests_total",
+            "JSON-RPC calls by chain and method",
+            "counter",
+        );
+        for ((chain, method), calls) in calls.iter() {
+            _ = writeln!(
+                out,
+                "nexum_rpc_requests_total{{chain=\"{chain}\",method=\"{method}\"}} {}",
+                calls.latency.count
+            );
+        }
+        header(
+            &mut out,
+            "nexum_rpc_errors_total",
+            "JSON-RPC calls answered with an error by chain and method",
+            "counter",
+        );
+        for ((chain, method), calls) in calls.iter() {
+            _ = writeln!(
+                out,
+                "nexum_rpc_errors_total{{chain=\"{chain}\",method=\"{method}\"}} {}",
+                calls.errors
+            );
+        }
+        header(
+            &mut out,
+            "nexum_rpc_request_duration_seconds",
+            "Latency of the JSON-RPC calls by chain and method",
+            "histogram",
+        );
+        for ((chain, method), calls) in calls.iter() {
+            calls.latency.render(
+                &mut out,
+                "nexum_rpc_request_duration_seconds",
+                &format!("chain=\"{chain}\",method=\"{method}\""),
+                &LATENCY_BUCKETS,
+            );
+        }
+        drop(calls);
+
+        header(
+            &mut out,
+            "nexum_prompt_decision_seconds",
+            "Time users took to answer prompts by method",
+            "histogram",
+        );
+        for (method, decisions) in self.0.lock_decisions().iter() {
+            decisions.render(
+                &mut out,
+                "nexum_prompt_decision_seconds",
+                &format!("method=\"{method}\""),
+                &DECISION_BUCKETS,
+            );
+        }
+
+        let caches = caches.iter().collect::<BTreeMap<_, _>>();
+        let cache_counters = [
+            (
+                "nexum_cache_hits_total",
+                "Upstream requests answered from the cache",
+                caches
+                    .iter()
+                    .map(|(chain, stats)| (*chain, stats.hits))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "nexum_cache_misses_total",
+                "Cacheable upstream requests that went upstream",
+                caches
+                    .iter()
+                    .map(|(chain, stats)| (*chain, stats.misses))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "nexum_cache_coalesced_total",
+                "Upstream requests that joined an identical in-flight request",
+                caches
+                    .iter()
+                    .map(|(chain, stats)| (*chain, stats.coalesced))
+                    .collect::<Vec<_>>(),
+            ),
+        ];
+        for (name, help, values) in cache_counters {
+            header(&mut out, name, help, "counter");
+            for (chain, value) in values {
+                _ = writeln!(out, "{name}{{chain=\"{chain}\"}} {value}");
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    _ = writeln!(out, "# HELP {name} {help}");
+    _ = writeln!(out, "# TYPE {name} {kind}");
+}
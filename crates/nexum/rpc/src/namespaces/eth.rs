@@ -1,27 +1,34 @@
 use alloy::{
-    consensus::EthereumTxEnvelope,
+    consensus::{EthereumTxEnvelope, Transaction},
     dyn_abi::TypedData,
-    network::{Ethereum, Network, NetworkWallet},
-    primitives::{Address, Bytes, TxHash},
+    eips::Encodable2718,
+    network::{Ethereum, Network, NetworkWallet, TransactionBuilder, TransactionBuilderError},
+    primitives::{Address, Bytes, TxHash, U64},
     providers::{
-        Provider,
+        PendingTransactionBuilder, Provider, RootProvider,
         fillers::{TxFiller, WalletFiller},
     },
     rpc::types::TransactionRequest,
+    transports::{RpcError, TransportError},
 };
+use alloy_chains::NamedChain;
 use jsonrpsee::{
-    RpcModule,
-    core::RpcResult,
-    types::{ErrorCode, ErrorObject},
+    Extensions, PendingSubscriptionSink, RpcModule, SubscriptionMessage,
+    core::{RpcResult, SubscriptionResult},
 };
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use url::Url;
 
 use crate::{
+    error::WalletError,
+    nonces::Reservation,
     rpc::{
-        GlobalRpcContext, InteractiveRequest, InteractiveResponse, json_rpc_internal_error,
-        make_interactive_request,
+        BatchTransaction, GlobalRpcContext, InteractiveRequest, InteractiveResponse,
+        LegacyTypedData, PromptRecorder, Session, batch_approved, batch_transaction,
+        make_interactive_request, prompt_user, session,
     },
+    simulation::simulate,
     upstream_requests,
 };
 
@@ -70,151 +77,381 @@ where
 
     eth_module.register_async_method(
         "eth_requestAccounts",
-        async |_, ctx, _| -> RpcResult<Vec<Address>> {
-            match make_interactive_request(
-                ctx.sender.clone(),
-                InteractiveRequest::EthRequestAccounts,
-            )
-            .await
-            .map_err(json_rpc_internal_error)?
-            {
-                InteractiveResponse::EthRequestAccounts(accounts) => Ok(accounts),
-                _ => Err(ErrorObject::from(ErrorCode::InternalError)),
-            }
-        },
+        async |_, ctx, ext| -> RpcResult<Vec<Address>> { request_accounts(&ctx, &ext).await },
     )?;
 
     eth_module.register_async_method(
         "eth_accounts",
-        async |_, ctx, _| -> RpcResult<Vec<Address>> {
-            match make_interactive_request(ctx.sender.clone(), InteractiveRequest::EthAccounts)
-                .await
-                .map_err(json_rpc_internal_error)?
-            {
-                InteractiveResponse::EthAccounts(accounts) => Ok(accounts),
-                _ => Err(ErrorObject::from(ErrorCode::InternalError)),
-            }
+        async |_, ctx, ext| -> RpcResult<Vec<Address>> {
+            accounts(&ctx, session(&ext)?.origin()).await
         },
     )?;
 
     eth_module.register_async_method(
         "eth_sendTransaction",
-        async |params, ctx, _| -> RpcResult<TxHash> {
-            let tx_req: TransactionRequest = params.one()?;
-            match make_interactive_request(
-                ctx.sender.clone(),
-                InteractiveRequest::EthRequestAccounts,
-            )
-            .await
-            .map_err(json_rpc_internal_error)?
-            {
-                InteractiveResponse::EthRequestAccounts(items) => {
-                    if let Some(signer_addr) = items.first() {
-                        let provider = (*ctx.provider).clone();
-                        let provider = provider.join_with(WalletFiller::new(RpcSigner::new(
-                            *signer_addr,
-                            ctx.sender.clone(),
-                        )));
-                        let tx = provider
-                            .send_transaction(tx_req)
-                            .await
-                            .map_err(json_rpc_internal_error)?;
-                        Ok(*tx.tx_hash())
-                    } else {
-                        Err(ErrorObject::from(ErrorCode::InternalError))
-                    }
-                }
-                _ => Err(ErrorObject::from(ErrorCode::InternalError)),
-            }
+        async |params, ctx, ext| -> RpcResult<TxHash> {
+            let (signer, tx_req) = transaction_signer(&ctx, &ext, params.one()?).await?;
+            let tx = send_transaction(&ctx, signer, tx_req).await?;
+            Ok(*tx.tx_hash())
         },
     )?;
 
     eth_module.register_async_method(
         "eth_signTransaction",
-        async |params, ctx, _| -> RpcResult<Bytes> {
-            let tx_req: TransactionRequest = params.one()?;
-            match make_interactive_request(
-                ctx.sender.clone(),
-                InteractiveRequest::EthRequestAccounts,
-            )
-            .await
-            .map_err(json_rpc_internal_error)?
-            {
-                InteractiveResponse::EthRequestAccounts(items) => {
-                    if let Some(signer_addr) = items.first() {
-                        let provider = (*ctx.provider).clone();
-                        let provider = provider.join_with(WalletFiller::new(RpcSigner::new(
-                            *signer_addr,
-                            ctx.sender.clone(),
-                        )));
-                        let signed_encoded_tx = provider
-                            .sign_transaction(tx_req)
-                            .await
-                            .map_err(json_rpc_internal_error)?;
-                        Ok(signed_encoded_tx)
-                    } else {
-                        Err(ErrorObject::from(ErrorCode::InternalError))
-                    }
-                }
-                _ => Err(ErrorObject::from(ErrorCode::InternalError)),
-            }
+        async |params, ctx, ext| -> RpcResult<Bytes> {
+            let (signer, tx_req) = transaction_signer(&ctx, &ext, params.one()?).await?;
+            Ok(sign_transaction(&ctx, signer, tx_req).await?)
         },
     )?;
 
-    eth_module.register_async_method("eth_sign", async |params, ctx, _| -> RpcResult<Bytes> {
+    eth_module.register_async_method("eth_sign", async |params, ctx, ext| -> RpcResult<Bytes> {
         let (signer_addr, message) = params.parse::<(Address, Bytes)>()?;
-        let (sender, receiver) = oneshot::channel::<InteractiveResponse>();
-        ctx.sender
-            .send((InteractiveRequest::EthSign(signer_addr, message), sender))
-            .await
-            .map_err(json_rpc_internal_error)?;
-        let res = receiver.await.map_err(json_rpc_internal_error)?;
-        match res {
-            InteractiveResponse::EthSign(signature) => Ok(signature
-                .map(|s| s.as_bytes().into())
-                .map_err(json_rpc_internal_error)?),
-            _ => Err(ErrorObject::from(ErrorCode::InternalError)),
-        }
+        sign_message(&ctx, &ext, signer_addr, message).await
     })?;
 
-    eth_module.register_async_method(
-        "eth_signTypedData_v4",
-        async |params, ctx, _| -> RpcResult<Bytes> {
-            let (signer_addr, typed_data) = params.parse::<(Address, TypedData)>()?;
-            let (sender, receiver) = oneshot::channel::<InteractiveResponse>();
-            ctx.sender
-                .send((
-                    InteractiveRequest::EthSignTypedData(signer_addr, typed_data.into()),
-                    sender,
-                ))
-                .await
-                .map_err(json_rpc_internal_error)?;
-            let res = receiver.await.map_err(json_rpc_internal_error)?;
-            match res {
-                InteractiveResponse::EthSignTypedData(signature) => Ok(signature
-                    .map(|s| s.as_bytes().into())
-                    .map_err(json_rpc_internal_error)?),
-                _ => Err(ErrorObject::from(ErrorCode::InternalError)),
+    eth_module.register_subscription(
+        "eth_subscribe",
+        "eth_subscription",
+        "eth_unsubscribe",
+        async |params, pending, _, ext| -> SubscriptionResult {
+            subscribe(params.parse()?, pending, session(&ext)?).await
+        },
+    )?;
+
+    // v3 is the subset of v4 without arrays and recursive structs, both hash as EIP-712
+    for method in ["eth_signTypedData_v3", "eth_signTypedData_v4"] {
+        eth_module.register_async_method(method, async |params, ctx, ext| -> RpcResult<Bytes> {
+            let (signer_addr, typed_data) = params.parse::<(Address, serde_json::Value)>()?;
+            // some dapps send the typed data as a JSON encoded string
+            let typed_data: TypedData = match typed_data {
+                serde_json::Value::String(typed_data) => serde_json::from_str(&typed_data),
+                typed_data => serde_json::from_value(typed_data),
             }
+            .map_err(|e| WalletError::InvalidParams(e.to_string()))?;
+            sign_typed_data(
+                &ctx,
+                &ext,
+                signer_addr,
+                InteractiveRequest::EthSignTypedData(signer_addr, typed_data.into()),
+            )
+            .await
+        })?;
+    }
+
+    eth_module.register_async_method(
+        "eth_signTypedData",
+        async |params, ctx, ext| -> RpcResult<Bytes> {
+            let (typed_data, signer_addr) = params.parse::<(LegacyTypedData, Address)>()?;
+            // fail early instead of prompting for data that can't be hashed
+            typed_data
+                .signature_hash()
+                .map_err(|e| WalletError::InvalidParams(e.to_string()))?;
+            sign_typed_data(
+                &ctx,
+                &ext,
+                signer_addr,
+                InteractiveRequest::EthSignLegacyTypedData(signer_addr, typed_data.into()),
+            )
+            .await
         },
     )?;
     Ok(eth_module)
 }
 
+/// Kinds of `eth_subscribe` subscriptions that are proxied to the upstream
+const SUBSCRIPTION_KINDS: [&str; 3] = ["newHeads", "logs", "newPendingTransactions"];
+
+/// Proxies the subscription to the upstream of the session's active chain and forwards the
+/// notifications until the subscription or the session is closed. Once the session switches to
+/// another chain the subscription ends with an error, the client has to subscribe again.
+async fn subscribe(
+    params: serde_json::Value,
+    pending: PendingSubscriptionSink,
+    session: &Session,
+) -> SubscriptionResult {
+    let kind = params.get(0).and_then(serde_json::Value::as_str);
+    if !kind.is_some_and(|kind| SUBSCRIPTION_KINDS.contains(&kind)) {
+        pending
+            .reject(WalletError::InvalidParams(format!(
+                "unsupported subscription {kind:?}"
+            )))
+            .await;
+        return Ok(());
+    }
+    let chain = session.active_chain();
+    let Some(ctx) = session.chains().context(chain) else {
+        pending
+            .reject(WalletError::ChainDisconnected(U64::from(chain as u64)))
+            .await;
+        return Ok(());
+    };
+
+    let (key, mut notifications) = match ctx.subscriptions.subscribe(&ctx.upstreams, params).await {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            pending.reject(provider_error(chain, e)).await;
+            return Ok(());
+        }
+    };
+    let sink = match pending.accept().await {
+        Ok(sink) => sink,
+        Err(_) => {
+            drop(notifications);
+            ctx.subscriptions.release(&key).await;
+            return Ok(());
+        }
+    };
+
+    let mut switched = false;
+    loop {
+        tokio::select! {
+            _ = sink.closed() => break,
+            _ = session.closed() => break,
+            _ = session.switched_from(chain) => {
+                switched = true;
+                break;
+            }
+            notification = notifications.recv() => match notification {
+                Ok(notification) => {
+                    if sink.send(SubscriptionMessage::from(serde_json::value::to_raw_value(&notification)?)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(key, n, "subscription lagged, notifications were skipped");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    drop(notifications);
+    ctx.subscriptions.release(&key).await;
+    if switched {
+        return Err(format!(
+            "subscription closed, the session switched from chain {} to chain {}",
+            chain as u64,
+            session.active_chain() as u64
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Signs the typed data of the request with the signer, once the user approves it
+async fn sign_typed_data<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    ext: &Extensions,
+    signer_addr: Address,
+    request: InteractiveRequest,
+) -> RpcResult<Bytes>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    let request = request.approved_if(batch_approved(ext)?);
+    authorize_signer(ctx, session(ext)?.origin(), signer_addr).await?;
+    match prompt_user(ctx.sender.clone(), ext, request).await? {
+        InteractiveResponse::EthSignTypedData(signature) => {
+            Ok(signature.map(|s| s.as_bytes().into())?)
+        }
+        _ => Err(WalletError::unexpected_response().into()),
+    }
+}
+
+/// Signs the EIP-191 message with the signer, once the user approves it
+pub(crate) async fn sign_message<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    ext: &Extensions,
+    signer_addr: Address,
+    message: Bytes,
+) -> RpcResult<Bytes>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    let request =
+        InteractiveRequest::EthSign(signer_addr, message).approved_if(batch_approved(ext)?);
+    authorize_signer(ctx, session(ext)?.origin(), signer_addr).await?;
+    match prompt_user(ctx.sender.clone(), ext, request).await? {
+        InteractiveResponse::EthSign(signature) => Ok(signature.map(|s| s.as_bytes().into())?),
+        _ => Err(WalletError::unexpected_response().into()),
+    }
+}
+
+/// Requests access to the accounts for the origin of the session, the user is prompted if the
+/// origin hasn't been granted access yet
+pub(crate) async fn request_accounts<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    ext: &Extensions,
+) -> RpcResult<Vec<Address>>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    let origin = session(ext)?.origin();
+    let granted = accounts(ctx, origin).await?;
+    if !granted.is_empty() {
+        return Ok(granted);
+    }
+    match prompt_user(
+        ctx.sender.clone(),
+        ext,
+        InteractiveRequest::EthRequestAccounts(origin.cloned()),
+    )
+    .await?
+    {
+        InteractiveResponse::EthRequestAccounts(Some(accounts)) => Ok(accounts),
+        InteractiveResponse::EthRequestAccounts(None) => Err(WalletError::UserRejected.into()),
+        _ => Err(WalletError::unexpected_response().into()),
+    }
+}
+
+/// Returns the accounts the origin has been granted access to
+pub(crate) async fn accounts<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    origin: Option<&Url>,
+) -> RpcResult<Vec<Address>>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    match make_interactive_request(
+        ctx.sender.clone(),
+        InteractiveRequest::EthAccounts(origin.cloned()),
+    )
+    .await?
+    {
+        InteractiveResponse::EthAccounts(accounts) => Ok(accounts),
+        _ => Err(WalletError::unexpected_response().into()),
+    }
+}
+
+/// Fails with the EIP-1193 unauthorized error if the origin can't access the signer
+pub(crate) async fn authorize_signer<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    origin: Option<&Url>,
+    signer: Address,
+) -> RpcResult<()>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    match make_interactive_request(
+        ctx.sender.clone(),
+        InteractiveRequest::AuthorizeSigner(origin.cloned(), signer),
+    )
+    .await?
+    {
+        InteractiveResponse::AuthorizeSigner(true) => Ok(()),
+        InteractiveResponse::AuthorizeSigner(false) => Err(WalletError::Unauthorized.into()),
+        _ => Err(WalletError::unexpected_response().into()),
+    }
+}
+
+/// Returns the account to sign with, `from` if the origin of the session can access it or else
+/// the first account the origin is granted
+pub(crate) async fn resolve_signer<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    ext: &Extensions,
+    from: Option<Address>,
+) -> RpcResult<Address>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    match from {
+        Some(from) => {
+            authorize_signer(ctx, session(ext)?.origin(), from).await?;
+            Ok(from)
+        }
+        None => request_accounts(ctx, ext)
+            .await?
+            .first()
+            .copied()
+            .ok_or_else(|| WalletError::Unauthorized.into()),
+    }
+}
+
+/// Returns the signer of a transaction sent by the client, along with the transaction to sign.
+/// The transaction of an approved batch is signed as the user reviewed it, with its nonce. Any
+/// other transaction prompts the user, even in an approved batch if it couldn't be reviewed.
+async fn transaction_signer<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    ext: &Extensions,
+    mut tx_req: TransactionRequest,
+) -> RpcResult<(RpcSigner, TransactionRequest)>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    // the calls of a rejected batch fail without prompting
+    batch_approved(ext)?;
+    let origin = session(ext)?.origin().cloned();
+    let signer_addr = resolve_signer(ctx, ext, tx_req.from).await?;
+    match batch_transaction(ext).filter(|reviewed| reviewed.from == signer_addr) {
+        Some(reviewed) => Ok((
+            RpcSigner::approved(signer_addr, origin, ctx.sender.clone()),
+            TransactionRequest::from_transaction_with_sender((*reviewed.tx).clone(), signer_addr),
+        )),
+        None => {
+            tx_req.from = Some(signer_addr);
+            let signer = RpcSigner::new(
+                signer_addr,
+                origin,
+                ctx.sender.clone(),
+                ctx.upstreams.provider(),
+                ext.get::<PromptRecorder>().cloned(),
+            );
+            Ok((signer, tx_req))
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-struct RpcSigner {
+pub(crate) struct RpcSigner {
     signer_addr: Address,
+    /// Origin the transactions are signed for, shown to the user and kept in their history
+    origin: Option<Url>,
     sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    /// Whether the user already approved the transactions, e.g. as part of a call batch
+    approved: bool,
+    /// Simulates the transactions before prompting, `None` if they aren't prompted for
+    simulator: Option<RootProvider>,
+    /// Records the prompts of the call the transactions are signed for
+    prompts: Option<PromptRecorder>,
 }
 
 impl RpcSigner {
-    fn new(
+    pub(crate) fn new(
         signer_addr: Address,
+        origin: Option<Url>,
         sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+        simulator: RootProvider,
+        prompts: Option<PromptRecorder>,
     ) -> Self {
         Self {
             sender,
             signer_addr,
+            origin,
+            approved: false,
+            simulator: Some(simulator),
+            prompts,
+        }
+    }
+
+    /// Signer for transactions the user has already approved, they are signed without prompting
+    pub(crate) fn approved(
+        signer_addr: Address,
+        origin: Option<Url>,
+        sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    ) -> Self {
+        Self {
+            sender,
+            signer_addr,
+            origin,
+            approved: true,
+            simulator: None,
+            prompts: None,
         }
     }
 }
@@ -229,8 +466,157 @@ enum RpcSignerError {
     UnexpectedResponse,
     #[error("sending signature request failed")]
     SendingSignatureRequestFailed,
-    #[error("signing error: {0:?}")]
-    SigningError(Box<dyn std::error::Error + Send + Sync>),
+    #[error("signing error: {0}")]
+    SigningError(WalletError),
+}
+
+impl From<&RpcSignerError> for WalletError {
+    fn from(err: &RpcSignerError) -> Self {
+        match err {
+            RpcSignerError::SignerAddressMismatch => Self::Unauthorized,
+            RpcSignerError::SignatureResponseChannelDropped
+            | RpcSignerError::SendingSignatureRequestFailed => Self::Disconnected,
+            RpcSignerError::UnexpectedResponse => Self::unexpected_response(),
+            RpcSignerError::SigningError(e) => e.clone(),
+        }
+    }
+}
+
+/// Maps the error of a request to the provider of the chain, recovering the error of the
+/// [`RpcSigner`] if signing failed
+pub(crate) fn provider_error(chain: NamedChain, err: TransportError) -> WalletError {
+    match err {
+        RpcError::LocalUsageError(e) => e
+            .downcast_ref::<TransactionBuilderError<Ethereum>>()
+            .and_then(|e| match e {
+                TransactionBuilderError::Signer(alloy::signers::Error::Other(e)) => {
+                    e.downcast_ref::<RpcSignerError>()
+                }
+                _ => None,
+            })
+            .map(WalletError::from)
+            .unwrap_or_else(|| WalletError::internal(e)),
+        e => WalletError::upstream(chain, e),
+    }
+}
+
+/// Signs the transaction and broadcasts it. Unless the client set its nonce, it's sent with the
+/// next nonce of the account not taken by the transactions sent before it.
+pub(crate) async fn send_transaction<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    signer: RpcSigner,
+    mut tx_req: TransactionRequest,
+) -> Result<PendingTransactionBuilder<Ethereum>, WalletError>
+where
+    P: Provider + Clone,
+    F: TxFiller,
+{
+    let account = signer.signer_addr;
+    let _reservation = reserve_nonce(ctx, account, &mut tx_req).await?;
+    let provider = (*ctx.provider).clone().join_with(WalletFiller::new(signer));
+    let envelope = provider
+        .fill(tx_req)
+        .await
+        .map_err(|e| provider_error(ctx.chain, e))?
+        .try_into_envelope()
+        .map_err(WalletError::internal)?;
+    // the user may have edited the nonce before signing
+    let nonce = envelope.nonce();
+    let tx = provider
+        .send_tx_envelope(envelope)
+        .await
+        .map_err(|e| provider_error(ctx.chain, e))?;
+    ctx.nonces.broadcast(ctx.chain, account, nonce);
+    Ok(tx)
+}
+
+/// Signs the transaction for the client to broadcast it. Its nonce is taken like the one of a
+/// transaction sent by the wallet, until the upstream counts it or it's considered dropped.
+async fn sign_transaction<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    signer: RpcSigner,
+    mut tx_req: TransactionRequest,
+) -> Result<Bytes, WalletError>
+where
+    P: Provider + Clone,
+    F: TxFiller,
+{
+    let account = signer.signer_addr;
+    let _reservation = reserve_nonce(ctx, account, &mut tx_req).await?;
+    let provider = (*ctx.provider).clone().join_with(WalletFiller::new(signer));
+    let envelope = provider
+        .fill(tx_req)
+        .await
+        .map_err(|e| provider_error(ctx.chain, e))?
+        .try_into_envelope()
+        .map_err(WalletError::internal)?;
+    ctx.nonces.broadcast(ctx.chain, account, envelope.nonce());
+    Ok(envelope.encoded_2718().into())
+}
+
+/// Reserves the nonce of the transaction unless the client set it. The nonce is held while the
+/// user is prompted, the transactions signed meanwhile take the next ones.
+async fn reserve_nonce<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    account: Address,
+    tx_req: &mut TransactionRequest,
+) -> Result<Option<Reservation>, WalletError>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    if tx_req.nonce.is_some() {
+        return Ok(None);
+    }
+    let reservation = ctx
+        .nonces
+        .reserve(ctx.chain, &*ctx.provider, account)
+        .await
+        .map_err(|e| WalletError::upstream(ctx.chain, e))?;
+    tx_req.nonce = Some(reservation.nonce());
+    Ok(Some(reservation))
+}
+
+/// Fills and simulates the transaction of a JSON-RPC batch call for the user to review it. It's
+/// sent from the first account of the origin unless the call sets `from`, and its nonce is held
+/// until the call signs it.
+pub(crate) async fn prepare_batch_transaction<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    accounts: &[Address],
+    params: &serde_json::Value,
+) -> Result<BatchTransaction, WalletError>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    let (mut tx_req,) = serde_json::from_value::<(TransactionRequest,)>(params.clone())
+        .map_err(|e| WalletError::InvalidParams(e.to_string()))?;
+    let from = tx_req
+        .from
+        .or_else(|| accounts.first().copied())
+        .ok_or(WalletError::Unauthorized)?;
+    tx_req.from = Some(from);
+    let reservation = reserve_nonce(ctx, from, &mut tx_req).await?;
+    let tx = ctx
+        .provider
+        .fill(tx_req)
+        .await
+        .map_err(|e| WalletError::upstream(ctx.chain, e))?
+        .try_into_request()
+        .map_err(|_| WalletError::Internal("filled transaction is already signed".to_string()))?
+        .build_unsigned()
+        .map_err(WalletError::internal)?;
+    let simulation = simulate(
+        &ctx.upstreams.provider(),
+        TransactionRequest::from_transaction_with_sender(tx.clone(), from),
+    )
+    .await;
+    Ok(BatchTransaction {
+        from,
+        tx: Box::new(tx),
+        simulation,
+        _reservation: reservation.map(Arc::new),
+    })
 }
 
 impl NetworkWallet<Ethereum> for RpcSigner {
@@ -274,12 +660,32 @@ impl NetworkWallet<Ethereum> for RpcSigner {
             return Err(alloy_err!(RpcSignerError::SignerAddressMismatch));
         }
 
+        let simulation = match &self.simulator {
+            Some(simulator) => Some(
+                simulate(
+                    simulator,
+                    TransactionRequest::from_transaction_with_sender(tx.clone(), sender),
+                )
+                .await,
+            ),
+            None => None,
+        };
+        let request = InteractiveRequest::SignTransaction(
+            self.origin.clone(),
+            self.signer_addr,
+            Box::new(tx.clone()),
+            simulation,
+        )
+        .approved_if(self.approved);
+        // the transaction is pending while the user is prompted, not while it's simulated
+        let _pending = self
+            .prompts
+            .as_ref()
+            .filter(|_| !self.approved)
+            .map(PromptRecorder::record);
         let (sender, receiver) = oneshot::channel();
         self.sender
-            .send((
-                InteractiveRequest::SignTransaction(Box::new(tx.clone())),
-                sender,
-            ))
+            .send((request, sender))
             .await
             .map_err(map_err!(RpcSignerError::SendingSignatureRequestFailed))?;
 
@@ -287,8 +693,9 @@ impl NetworkWallet<Ethereum> for RpcSigner {
             .await
             .map_err(map_err!(RpcSignerError::SignatureResponseChannelDropped))?;
         match response {
-            InteractiveResponse::SignTransaction(Ok(sig)) => {
-                Ok(EthereumTxEnvelope::new_unhashed(tx, sig))
+            // the user may have edited the gas, fees or nonce, what they approved is signed
+            InteractiveResponse::SignTransaction(Ok((tx, sig))) => {
+                Ok(EthereumTxEnvelope::new_unhashed(*tx, sig))
             }
             InteractiveResponse::SignTransaction(Err(e)) => {
                 Err(alloy_err!(RpcSignerError::SigningError(e)))
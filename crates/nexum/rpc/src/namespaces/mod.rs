@@ -1,5 +1,7 @@
 pub mod eth;
 pub mod net;
+pub mod nexum;
+pub mod personal;
 pub mod wallet;
 pub mod web3;
 
@@ -14,8 +16,8 @@ macro_rules! upstream_request {
             _: jsonrpsee::Extensions,
         ) -> jsonrpsee::core::RpcResult<serde_json::Value>
         where
-            P: alloy::providers::Provider,
-            F: alloy::providers::fillers::TxFiller,
+            P: alloy::providers::Provider + 'static,
+            F: alloy::providers::fillers::TxFiller + 'static,
         {
             let params: Result<$crate::rpc::RequestParams, _> = params.parse();
             tracing::trace!("Received request extension");
@@ -30,16 +32,19 @@ macro_rules! upstream_request {
                 Err(_) => return Err(jsonrpsee::types::ErrorObject::from(jsonrpsee::types::ErrorCode::ParseError)),
             };
 
-            // Perform the request
-            let response: Result<serde_json::Value, alloy::transports::RpcError<alloy::transports::TransportErrorKind>> = context.provider
-                .raw_request(std::borrow::Cow::Borrowed($method_name), params)
-                .await;
+            // Perform the request, unless the result is cached or already being fetched
+            let upstream = context.clone();
+            let upstream_params = params.clone();
+            let response = context.cache.request($method_name, &params, move || async move {
+                // Pass node-side errors through, e.g. to keep the revert data of calls
+                upstream.provider
+                    .raw_request(std::borrow::Cow::Borrowed($method_name), upstream_params)
+                    .await
+                    .map_err(|e| $crate::error::WalletError::upstream(upstream.chain, e))
+            })
+            .await;
 
-            // Match the result and convert errors
-            match response {
-                Ok(res) => Ok(res),
-                Err(_) => Err(jsonrpsee::types::ErrorObject::from(jsonrpsee::types::ErrorCode::InternalError)),
-            }
+            response.map_err(Into::into)
         }
         }
     };
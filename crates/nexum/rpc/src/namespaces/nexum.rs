@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use alloy::{
+    primitives::Address,
+    providers::{Provider, fillers::TxFiller},
+};
+use jsonrpsee::{
+    PendingSubscriptionSink, RpcModule, SubscriptionMessage,
+    core::{RpcResult, SubscriptionResult},
+};
+use nexum_primitives::ChainStatus;
+use serde::Serialize;
+
+use crate::{
+    namespaces::eth::accounts,
+    rpc::{GlobalRpcContext, Session, session},
+    status::PendingRequest,
+};
+
+/// How often the status subscriptions check whether the upstreams of the chains went up or down
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// State of the wallet as seen by a session, sent to the subscribers of `nexum_subscribeStatus`
+/// whenever it changes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletStatus {
+    pub version: &'static str,
+    /// Unix time the server started at, in seconds
+    pub started_at: u64,
+    /// Active chain of the session
+    pub chain_id: u64,
+    pub chains: Vec<ChainStatus>,
+    /// Active account of the wallet, if the session's origin has been granted access to it
+    pub active_account: Option<Address>,
+    /// Number of requests of the session's origin waiting on the user
+    pub pending_requests: usize,
+}
+
+pub fn init<F, P>(
+    context: GlobalRpcContext<F, P>,
+) -> eyre::Result<RpcModule<GlobalRpcContext<F, P>>>
+where
+    P: Provider + 'static,
+    F: TxFiller + 'static,
+{
+    let mut nexum_module = RpcModule::new(context);
+
+    nexum_module.register_async_method(
+        "nexum_status",
+        async |_, ctx, ext| -> RpcResult<WalletStatus> { status(&ctx, session(&ext)?).await },
+    )?;
+
+    nexum_module.register_method("nexum_chains", |_, _, ext| -> RpcResult<Vec<ChainStatus>> {
+        Ok(chains(session(ext)?))
+    })?;
+
+    nexum_module.register_async_method(
+        "nexum_activeAccount",
+        async |_, ctx, ext| -> RpcResult<Option<Address>> {
+            active_account(&ctx, session(&ext)?).await
+        },
+    )?;
+
+    nexum_module.register_method(
+        "nexum_pendingRequests",
+        |_, ctx, ext| -> RpcResult<Vec<PendingRequest>> {
+            Ok(ctx.status.pending_requests(session(ext)?.origin()))
+        },
+    )?;
+
+    nexum_module.register_subscription(
+        "nexum_subscribeStatus",
+        "nexum_subscription",
+        "nexum_unsubscribeStatus",
+        async |_, pending, ctx, ext| -> SubscriptionResult {
+            subscribe_status(pending, &ctx, session(&ext)?).await
+        },
+    )?;
+
+    Ok(nexum_module)
+}
+
+/// Chains served by the wallet, by chain id
+fn chains(session: &Session) -> Vec<ChainStatus> {
+    session
+        .chains()
+        .states()
+        .into_iter()
+        .map(|(chain, connected)| ChainStatus {
+            chain_id: chain as u64,
+            name: chain.to_string(),
+            connected,
+        })
+        .collect()
+}
+
+/// Active account of the wallet, if the origin has been granted access to it
+async fn active_account<F, P>(
+    ctx: &GlobalRpcContext<F, P>,
+    session: &Session,
+) -> RpcResult<Option<Address>>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    Ok(accounts(ctx, session.origin()).await?.first().copied())
+}
+
+async fn status<F, P>(ctx: &GlobalRpcContext<F, P>, session: &Session) -> RpcResult<WalletStatus>
+where
+    P: Provider,
+    F: TxFiller,
+{
+    Ok(WalletStatus {
+        version: env!("CARGO_PKG_VERSION"),
+        started_at: ctx.status.started_at(),
+        chain_id: session.active_chain() as u64,
+        chains: chains(session),
+        active_account: active_account(ctx, session).await?,
+        pending_requests: ctx.status.pending_requests(session.origin()).len(),
+    })
+}
+
+/// Sends the status of the wallet, then the status again whenever it changes, until the
+/// subscription or the session is closed
+async fn subscribe_status<F, P>(
+    pending: PendingSubscriptionSink,
+    ctx: &GlobalRpcContext<F, P>,
+    session: &Session,
+) -> SubscriptionResult
+where
+    P: Provider,
+    F: TxFiller,
+{
+    let mut changes = ctx.status.subscribe();
+    let mut last = match status(ctx, session).await {
+        Ok(status) => status,
+        Err(e) => {
+            pending.reject(e).await;
+            return Ok(());
+        }
+    };
+    let Ok(sink) = pending.accept().await else {
+        return Ok(());
+    };
+    sink.send(SubscriptionMessage::from(serde_json::value::to_raw_value(
+        &last,
+    )?))
+    .await?;
+
+    // the health of the upstreams is probed in the background, it's checked periodically
+    let mut health_check = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = sink.closed() => break,
+            _ = session.closed() => break,
+            changed = changes.changed() => if changed.is_err() {
+                break;
+            },
+            _ = health_check.tick() => {}
+        }
+        // the active chain of the session isn't reported by the changes
+        let status = status(ctx, session).await?;
+        if status != last {
+            sink.send(SubscriptionMessage::from(serde_json::value::to_raw_value(
+                &status,
+            )?))
+            .await?;
+            last = status;
+        }
+    }
+    Ok(())
+}
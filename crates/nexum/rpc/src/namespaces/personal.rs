@@ -0,0 +1,88 @@
+use alloy::{
+    primitives::{Address, Bytes, Signature, hex},
+    providers::{Provider, fillers::TxFiller},
+};
+use jsonrpsee::{RpcModule, core::RpcResult};
+
+use crate::{
+    error::WalletError,
+    namespaces::eth::{accounts, sign_message},
+    rpc::{GlobalRpcContext, session},
+};
+
+pub fn init<F, P>(
+    context: GlobalRpcContext<F, P>,
+) -> eyre::Result<RpcModule<GlobalRpcContext<F, P>>>
+where
+    P: Provider + 'static,
+    F: TxFiller + 'static,
+{
+    let mut personal_module = RpcModule::new(context);
+
+    personal_module.register_async_method(
+        "personal_sign",
+        async |params, ctx, ext| -> RpcResult<Bytes> {
+            // the optional third parameter is a password, which nexum has no use for
+            let mut params = params.sequence();
+            let first: String = params.next()?;
+            let second: String = params.next()?;
+
+            // the spec puts the message first, but some dapps send the address first like
+            // `eth_sign` does. A message may look like an address too, then the signer is the
+            // one the origin has access to.
+            let (signer_addr, message) = match (second.parse::<Address>(), first.parse::<Address>())
+            {
+                (Ok(second_addr), Ok(first_addr)) => {
+                    let accounts = accounts(&ctx, session(&ext)?.origin()).await?;
+                    match (
+                        accounts.contains(&second_addr),
+                        accounts.contains(&first_addr),
+                    ) {
+                        (true, false) => (second_addr, first),
+                        (false, true) => (first_addr, second),
+                        (true, true) => {
+                            return Err(invalid_params(
+                                "both parameters are accounts, the signer is ambiguous",
+                            )
+                            .into());
+                        }
+                        (false, false) => return Err(WalletError::Unauthorized.into()),
+                    }
+                }
+                (Ok(signer_addr), Err(_)) => (signer_addr, first),
+                (Err(_), Ok(signer_addr)) => (signer_addr, second),
+                _ => return Err(invalid_params("none of the parameters is an address").into()),
+            };
+            sign_message(&ctx, &ext, signer_addr, message_bytes(message)).await
+        },
+    )?;
+
+    personal_module.register_method(
+        "personal_ecRecover",
+        |params, _, _| -> RpcResult<Address> {
+            let mut params = params.sequence();
+            let message: String = params.next()?;
+            let signature: Bytes = params.next()?;
+
+            let signature =
+                Signature::from_raw(&signature).map_err(|_| invalid_params("invalid signature"))?;
+            Ok(signature
+                .recover_address_from_msg(message_bytes(message))
+                .map_err(|_| invalid_params("failed to recover the signer"))?)
+        },
+    )?;
+
+    Ok(personal_module)
+}
+
+/// Messages are usually hex encoded, but dapps also send plain UTF-8 text
+fn message_bytes(message: String) -> Bytes {
+    match message.strip_prefix("0x").map(hex::decode) {
+        Some(Ok(bytes)) => bytes.into(),
+        _ => message.into_bytes().into(),
+    }
+}
+
+fn invalid_params(message: &str) -> WalletError {
+    WalletError::InvalidParams(message.to_string())
+}
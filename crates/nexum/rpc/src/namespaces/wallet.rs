@@ -1,15 +1,555 @@
-use alloy::providers::{Provider, fillers::TxFiller};
-use jsonrpsee::RpcModule;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
-use crate::rpc::GlobalRpcContext;
+use alloy::{
+    primitives::{Address, B256, Bytes, TxKind, U64, U256},
+    providers::{Provider, fillers::TxFiller},
+    rpc::types::{Log, TransactionInput, TransactionReceipt, TransactionRequest},
+    sol,
+};
+use alloy_chains::NamedChain;
+use jsonrpsee::{RpcModule, core::RpcResult};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    error::WalletError,
+    namespaces::eth::{
+        RpcSigner, authorize_signer, request_accounts, resolve_signer, send_transaction,
+    },
+    rpc::{
+        CallBatch, GlobalRpcContext, InteractiveRequest, InteractiveResponse, TokenMetadata,
+        batch_approved, make_interactive_request, prompt_user, session,
+    },
+};
+
+/// EIP-3326 `wallet_switchEthereumChain` parameter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwitchEthereumChainParameter {
+    chain_id: U64,
+}
+
+/// EIP-3085 `wallet_addEthereumChain` parameter, only the fields nexum makes use of
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddEthereumChainParameter {
+    chain_id: U64,
+    #[serde(default)]
+    rpc_urls: Vec<Url>,
+}
+
+/// EIP-747 `wallet_watchAsset` parameter
+#[derive(Debug, Deserialize)]
+struct WatchAssetParameter {
+    #[serde(rename = "type")]
+    type_: String,
+    options: WatchAssetOptions,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchAssetOptions {
+    address: Address,
+    symbol: String,
+    decimals: u8,
+}
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20Metadata {
+        function symbol() external view returns (string memory);
+        function decimals() external view returns (uint8);
+    }
+}
+
+/// EIP-5792 `wallet_sendCalls` parameter
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendCallsParameter {
+    id: Option<String>,
+    chain_id: U64,
+    from: Option<Address>,
+    #[serde(default)]
+    atomic_required: bool,
+    calls: Vec<Call>,
+    #[serde(default)]
+    capabilities: HashMap<String, CapabilityParameter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Call {
+    to: Option<Address>,
+    data: Option<Bytes>,
+    value: Option<U256>,
+    #[serde(default)]
+    capabilities: HashMap<String, CapabilityParameter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CapabilityParameter {
+    #[serde(default)]
+    optional: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SendCallsResult {
+    id: String,
+}
+
+/// EIP-5792 capabilities of a chain. The calls of a batch are sent one by one, so nexum can't
+/// execute them atomically.
+#[derive(Clone, Debug, Serialize)]
+struct Capabilities {
+    atomic: AtomicCapability,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct AtomicCapability {
+    status: &'static str,
+}
+
+/// EIP-5792 `wallet_getCallsStatus` result
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CallsStatus {
+    version: &'static str,
+    id: String,
+    chain_id: U64,
+    status: u16,
+    atomic: bool,
+    receipts: Vec<CallReceipt>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CallReceipt {
+    logs: Vec<CallLog>,
+    status: U64,
+    block_hash: Option<B256>,
+    block_number: Option<U64>,
+    gas_used: U64,
+    transaction_hash: B256,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct CallLog {
+    address: Address,
+    topics: Vec<B256>,
+    data: Bytes,
+}
+
+impl From<&TransactionReceipt> for CallReceipt {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        Self {
+            logs: receipt.inner.logs().iter().map(CallLog::from).collect(),
+            status: U64::from(receipt.status()),
+            block_hash: receipt.block_hash,
+            block_number: receipt.block_number.map(U64::from),
+            gas_used: U64::from(receipt.gas_used),
+            transaction_hash: receipt.transaction_hash,
+        }
+    }
+}
+
+impl From<&Log> for CallLog {
+    fn from(log: &Log) -> Self {
+        Self {
+            address: log.address(),
+            topics: log.topics().to_vec(),
+            data: log.data().data.clone(),
+        }
+    }
+}
+
+const CALLS_STATUS_VERSION: &str = "2.0.0";
+/// How long the receipts of a call batch are waited for before the batch is considered failed
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The only permission nexum grants, access to the accounts
+const ETH_ACCOUNTS_PERMISSION: &str = "eth_accounts";
+
+/// EIP-2255 permission, the accounts the origin can access are listed in the
+/// `restrictReturnedAccounts` caveat
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Permission {
+    invoker: Option<String>,
+    parent_capability: &'static str,
+    caveats: Vec<Caveat>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Caveat {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    value: Vec<Address>,
+}
+
+impl Permission {
+    /// Returns the `eth_accounts` permission, or `None` if no accounts are granted
+    fn eth_accounts(origin: Option<&Url>, accounts: Vec<Address>) -> Option<Self> {
+        (!accounts.is_empty()).then(|| Self {
+            invoker: origin.map(|origin| origin.origin().ascii_serialization()),
+            parent_capability: ETH_ACCOUNTS_PERMISSION,
+            caveats: vec![Caveat {
+                type_: "restrictReturnedAccounts",
+                value: accounts,
+            }],
+        })
+    }
+}
 
 pub fn init<F, P>(
     context: GlobalRpcContext<F, P>,
 ) -> eyre::Result<RpcModule<GlobalRpcContext<F, P>>>
 where
-    P: Provider + 'static,
+    P: Provider + Clone + 'static,
     F: TxFiller + 'static,
 {
-    let wallet_module = RpcModule::new(context);
+    let mut wallet_module = RpcModule::new(context);
+
+    wallet_module.register_async_method(
+        "wallet_switchEthereumChain",
+        async |params, _, ext| -> RpcResult<()> {
+            let SwitchEthereumChainParameter { chain_id } = params.one()?;
+            let chain = named_chain(chain_id)?;
+            if session(&ext)?.switch_chain(chain) {
+                Ok(())
+            } else {
+                Err(WalletError::UnrecognizedChain(chain_id).into())
+            }
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_addEthereumChain",
+        async |params, ctx, ext| -> RpcResult<()> {
+            let AddEthereumChainParameter { chain_id, rpc_urls } = params.one()?;
+            let chain = named_chain(chain_id)?;
+            let session = session(&ext)?;
+
+            // the chain is already served, adding it again is a no-op apart from the switch
+            if session.switch_chain(chain) {
+                return Ok(());
+            }
+
+            if rpc_urls.is_empty() {
+                return Err(WalletError::InvalidParams("no rpc url provided".to_string()).into());
+            }
+
+            match prompt_user(
+                ctx.sender.clone(),
+                &ext,
+                InteractiveRequest::WalletAddEthereumChain(chain, rpc_urls.clone())
+                    .approved_if(batch_approved(&ext)?),
+            )
+            .await?
+            {
+                InteractiveResponse::WalletAddEthereumChain(true) => {}
+                InteractiveResponse::WalletAddEthereumChain(false) => {
+                    return Err(WalletError::UserRejected.into());
+                }
+                _ => return Err(WalletError::unexpected_response().into()),
+            }
+
+            session
+                .chains()
+                .add(chain, rpc_urls)
+                .await
+                .map_err(WalletError::internal)?;
+            session.switch_chain(chain);
+            Ok(())
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_requestPermissions",
+        async |params, ctx, ext| -> RpcResult<Vec<Permission>> {
+            let requested: serde_json::Map<String, serde_json::Value> = params.one()?;
+            if let Some(unsupported) = requested.keys().find(|p| *p != ETH_ACCOUNTS_PERMISSION) {
+                return Err(WalletError::InvalidParams(format!(
+                    "unsupported permission {unsupported}"
+                ))
+                .into());
+            }
+
+            let origin = session(&ext)?.origin();
+            let accounts = request_accounts(&ctx, &ext).await?;
+            Ok(Permission::eth_accounts(origin, accounts)
+                .into_iter()
+                .collect())
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_getPermissions",
+        async |_, ctx, ext| -> RpcResult<Vec<Permission>> {
+            let origin = session(&ext)?.origin();
+            match make_interactive_request(
+                ctx.sender.clone(),
+                InteractiveRequest::WalletGetPermissions(origin.cloned()),
+            )
+            .await?
+            {
+                InteractiveResponse::WalletGetPermissions(accounts) => {
+                    Ok(Permission::eth_accounts(origin, accounts)
+                        .into_iter()
+                        .collect())
+                }
+                _ => Err(WalletError::unexpected_response().into()),
+            }
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_watchAsset",
+        async |params, ctx, ext| -> RpcResult<bool> {
+            // the parameter is an object, but some dapps wrap it in an array
+            let WatchAssetParameter { type_, options } =
+                params.parse().or_else(|_| params.one())?;
+            if type_ != "ERC20" {
+                return Err(
+                    WalletError::InvalidParams(format!("unsupported asset type {type_}")).into(),
+                );
+            }
+
+            let token = IERC20Metadata::new(options.address, ctx.provider.clone());
+            let not_erc20 = |_| {
+                WalletError::InvalidParams(format!("{} is not an ERC-20 token", options.address))
+            };
+            let symbol = token.symbol().call().await.map_err(not_erc20)?;
+            let decimals = token.decimals().call().await.map_err(not_erc20)?;
+            if symbol != options.symbol || decimals != options.decimals {
+                return Err(WalletError::InvalidParams(format!(
+                    "token metadata doesn't match the contract, expected {symbol} ({decimals})"
+                ))
+                .into());
+            }
+
+            match prompt_user(
+                ctx.sender.clone(),
+                &ext,
+                InteractiveRequest::WalletWatchAsset(
+                    ctx.chain,
+                    options.address,
+                    TokenMetadata { symbol, decimals },
+                )
+                .approved_if(batch_approved(&ext)?),
+            )
+            .await?
+            {
+                InteractiveResponse::WalletWatchAsset(tracked) => Ok(tracked),
+                _ => Err(WalletError::unexpected_response().into()),
+            }
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_getCapabilities",
+        async |params, ctx, ext| -> RpcResult<BTreeMap<String, Capabilities>> {
+            let mut params = params.sequence();
+            let account: Address = params.next()?;
+            let chain_ids: Option<Vec<U64>> = params.optional_next()?;
+            let session = session(&ext)?;
+            authorize_signer(&ctx, session.origin(), account).await?;
+
+            Ok(session
+                .chains()
+                .methods()
+                .chains()
+                .into_iter()
+                .filter(|chain| {
+                    chain_ids
+                        .as_ref()
+                        .is_none_or(|ids| ids.contains(&U64::from(*chain as u64)))
+                })
+                .map(|chain| {
+                    (
+                        format!("{:#x}", chain as u64),
+                        Capabilities {
+                            atomic: AtomicCapability {
+                                status: "unsupported",
+                            },
+                        },
+                    )
+                })
+                .collect())
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_sendCalls",
+        async |params, ctx, ext| -> RpcResult<SendCallsResult> {
+            let SendCallsParameter {
+                id,
+                chain_id,
+                from,
+                atomic_required,
+                calls,
+                capabilities,
+            } = params.one()?;
+            if chain_id != U64::from(ctx.chain as u64) {
+                return Err(WalletError::UnsupportedChainId(chain_id).into());
+            }
+            if atomic_required {
+                return Err(WalletError::AtomicityNotSupported.into());
+            }
+            // none of the capabilities are supported, only optional ones can be ignored
+            if let Some(capability) = capabilities
+                .iter()
+                .chain(calls.iter().flat_map(|call| &call.capabilities))
+                .find_map(|(name, capability)| (!capability.optional).then_some(name))
+            {
+                return Err(WalletError::UnsupportedCapability(capability.clone()).into());
+            }
+            if calls.is_empty() {
+                return Err(WalletError::InvalidParams("no calls provided".to_string()).into());
+            }
+            // the user isn't prompted for a batch that can't be sent, the id is taken once the
+            // batch is approved
+            if let Some(id) = id.as_ref().filter(|id| ctx.call_batches.contains(id)) {
+                return Err(WalletError::DuplicateBatchId(id.clone()).into());
+            }
+
+            let approved = batch_approved(&ext)?;
+            let origin = session(&ext)?.origin();
+            let signer_addr = resolve_signer(&ctx, &ext, from).await?;
+            let tx_reqs = calls
+                .into_iter()
+                .map(|call| TransactionRequest {
+                    from: Some(signer_addr),
+                    to: Some(call.to.map_or(TxKind::Create, TxKind::Call)),
+                    value: call.value,
+                    input: TransactionInput::maybe_input(call.data),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+
+            match prompt_user(
+                ctx.sender.clone(),
+                &ext,
+                InteractiveRequest::WalletSendCalls(ctx.chain, signer_addr, tx_reqs.clone())
+                    .approved_if(approved),
+            )
+            .await?
+            {
+                InteractiveResponse::WalletSendCalls(true) => {}
+                InteractiveResponse::WalletSendCalls(false) => {
+                    return Err(WalletError::UserRejected.into());
+                }
+                _ => return Err(WalletError::unexpected_response().into()),
+            }
+
+            let mut batch = CallBatch {
+                chain: ctx.chain,
+                calls: tx_reqs.len(),
+                tx_hashes: Vec::with_capacity(tx_reqs.len()),
+                receipts: HashMap::new(),
+                failed: false,
+            };
+            // two batches with the same id may have been approved meanwhile
+            if let Some(id) = &id
+                && !ctx.call_batches.try_insert(id.clone(), batch.clone())
+            {
+                return Err(WalletError::DuplicateBatchId(id.clone()).into());
+            }
+            let mut pending_txs = Vec::with_capacity(tx_reqs.len());
+            for tx_req in tx_reqs {
+                // the batch was approved as a whole, so the calls are signed without prompting
+                let signer = RpcSigner::approved(signer_addr, origin.cloned(), ctx.sender.clone());
+                match send_transaction(&ctx, signer, tx_req).await {
+                    Ok(pending_tx) => {
+                        batch.tx_hashes.push(*pending_tx.tx_hash());
+                        pending_txs.push(pending_tx);
+                    }
+                    // nothing was sent yet, the batch can simply fail
+                    Err(e) if batch.tx_hashes.is_empty() => {
+                        if let Some(id) = &id {
+                            ctx.call_batches.remove(id);
+                        }
+                        return Err(e.into());
+                    }
+                    Err(e) => {
+                        tracing::warn!(?e, "failed to send call, the batch is partial");
+                        break;
+                    }
+                }
+            }
+            // without an id from the app the hash of the first transaction is unique enough
+            let id = id.unwrap_or_else(|| batch.tx_hashes[0].to_string());
+            // replaces the batch the id was taken with
+            ctx.call_batches.insert(id.clone(), batch);
+
+            for pending_tx in pending_txs {
+                let call_batches = ctx.call_batches.clone();
+                let id = id.clone();
+                tokio::spawn(async move {
+                    match pending_tx
+                        .with_timeout(Some(RECEIPT_TIMEOUT))
+                        .get_receipt()
+                        .await
+                    {
+                        Ok(receipt) => call_batches.insert_receipt(&id, receipt),
+                        Err(e) => {
+                            tracing::warn!(?e, id, "failed to get call receipt, the batch failed");
+                            call_batches.fail(&id);
+                        }
+                    }
+                });
+            }
+
+            Ok(SendCallsResult { id })
+        },
+    )?;
+
+    wallet_module.register_async_method(
+        "wallet_getCallsStatus",
+        async |params, ctx, _| -> RpcResult<CallsStatus> {
+            let id: String = params.one()?;
+            let batch = ctx
+                .call_batches
+                .get(&id)
+                .ok_or_else(|| WalletError::UnknownBatchId(id.clone()))?;
+
+            let receipts = batch
+                .tx_hashes
+                .iter()
+                .filter_map(|tx_hash| batch.receipts.get(tx_hash))
+                .collect::<Vec<_>>();
+            let succeeded = receipts.iter().filter(|receipt| receipt.status()).count();
+            let status = if batch.failed && receipts.is_empty() {
+                // failed offchain
+                400
+            } else if !batch.failed
+                && (batch.tx_hashes.is_empty() || receipts.len() < batch.tx_hashes.len())
+            {
+                // pending, or still being sent
+                100
+            } else if succeeded == batch.calls {
+                // confirmed
+                200
+            } else if succeeded == 0 {
+                // reverted
+                500
+            } else {
+                // partially reverted
+                600
+            };
+
+            Ok(CallsStatus {
+                version: CALLS_STATUS_VERSION,
+                id,
+                chain_id: U64::from(batch.chain as u64),
+                status,
+                atomic: false,
+                receipts: receipts.into_iter().map(CallReceipt::from).collect(),
+            })
+        },
+    )?;
+
     Ok(wallet_module)
 }
+
+fn named_chain(chain_id: U64) -> Result<NamedChain, WalletError> {
+    NamedChain::try_from(chain_id.to::<u64>()).map_err(|_| WalletError::UnrecognizedChain(chain_id))
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use alloy::{primitives::Address, providers::Provider, transports::TransportResult};
+use alloy_chains::NamedChain;
+
+/// How long a broadcast transaction may go uncounted by the upstream before it's considered
+/// dropped, and its nonce reused
+const DROPPED_AFTER: Duration = Duration::from_secs(300);
+
+/// Nonces of the transactions sent through the wallet, by chain and account. The handle is shared
+/// by every connection, so transactions sent at the same time, by one client or several, don't
+/// reuse a nonce while the upstream doesn't count them yet.
+#[derive(Clone, Debug, Default)]
+pub struct Nonces(Arc<Mutex<HashMap<(NamedChain, Address), AccountNonces>>>);
+
+/// Nonces the upstream doesn't count yet in the pending transaction count of an account
+#[derive(Debug, Default)]
+struct AccountNonces(BTreeMap<u64, Taken>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Taken {
+    /// Handed out to a transaction being signed
+    Reserved,
+    Broadcast(Instant),
+}
+
+/// Nonce handed out to a transaction, released when dropped unless the transaction has been
+/// broadcast with it
+#[derive(Debug)]
+pub struct Reservation {
+    nonces: Nonces,
+    chain: NamedChain,
+    account: Address,
+    nonce: u64,
+}
+
+impl Reservation {
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let mut nonces = self.nonces.lock();
+        if let Some(account) = nonces.get_mut(&(self.chain, self.account))
+            && account.0.get(&self.nonce) == Some(&Taken::Reserved)
+        {
+            account.0.remove(&self.nonce);
+        }
+    }
+}
+
+impl Nonces {
+    /// Reserves the lowest nonce of the account neither counted by the upstream nor taken by
+    /// another transaction, which fills the gaps left by the transactions that weren't sent
+    pub async fn reserve<P: Provider>(
+        &self,
+        chain: NamedChain,
+        provider: &P,
+        account: Address,
+    ) -> TransportResult<Reservation> {
+        let count = provider.get_transaction_count(account).pending().await?;
+        let mut nonces = self.lock();
+        let account_nonces = nonces.entry((chain, account)).or_default();
+        account_nonces.resync(chain, account, count);
+        let nonce = account_nonces.next(count);
+        account_nonces.0.insert(nonce, Taken::Reserved);
+        Ok(Reservation {
+            nonces: self.clone(),
+            chain,
+            account,
+            nonce,
+        })
+    }
+
+    /// Records the transaction of the account broadcast with the nonce, which isn't handed out
+    /// again until the upstream counts it
+    pub fn broadcast(&self, chain: NamedChain, account: Address, nonce: u64) {
+        self.lock()
+            .entry((chain, account))
+            .or_default()
+            .0
+            .insert(nonce, Taken::Broadcast(Instant::now()));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<(NamedChain, Address), AccountNonces>> {
+        self.0.lock().expect("failed to lock nonces")
+    }
+}
+
+impl AccountNonces {
+    /// Forgets the nonces counted by the upstream, and the transactions it lost track of
+    fn resync(&mut self, chain: NamedChain, account: Address, count: u64) {
+        self.0 = self.0.split_off(&count);
+        self.0.retain(|nonce, taken| match taken {
+            Taken::Broadcast(at) if at.elapsed() > DROPPED_AFTER => {
+                tracing::warn!(%chain, %account, nonce, "transaction dropped, its nonce is reused");
+                false
+            }
+            _ => true,
+        });
+        let next = self.next(count);
+        if let Some(last) = self.0.keys().next_back()
+            && next < *last
+        {
+            tracing::warn!(
+                %chain,
+                %account,
+                gap = next,
+                "nonce gap, the transactions after it are stuck until it's filled"
+            );
+        }
+    }
+
+    /// Lowest nonce from the count that isn't taken
+    fn next(&self, count: u64) -> u64 {
+        let mut next = count;
+        for nonce in self.0.keys() {
+            if *nonce != next {
+                break;
+            }
+            next += 1;
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::U64,
+        providers::{ProviderBuilder, mock::Asserter},
+    };
+
+    use super::*;
+
+    fn account_nonces(taken: impl IntoIterator<Item = (u64, Taken)>) -> AccountNonces {
+        AccountNonces(taken.into_iter().collect())
+    }
+
+    #[test]
+    fn next_fills_the_gaps() {
+        assert_eq!(account_nonces([]).next(5), 5);
+        let nonces = account_nonces([(5, Taken::Reserved), (6, Taken::Reserved)]);
+        assert_eq!(nonces.next(5), 7);
+        let nonces = account_nonces([(5, Taken::Reserved), (7, Taken::Reserved)]);
+        assert_eq!(nonces.next(5), 6);
+        assert_eq!(nonces.next(4), 4);
+    }
+
+    #[test]
+    fn resync_forgets_counted_and_dropped_nonces() {
+        let dropped_at = Instant::now() - DROPPED_AFTER - Duration::from_secs(1);
+        let mut nonces = account_nonces([
+            (4, Taken::Broadcast(Instant::now())),
+            (5, Taken::Broadcast(dropped_at)),
+            (6, Taken::Broadcast(Instant::now())),
+            (7, Taken::Reserved),
+        ]);
+        nonces.resync(NamedChain::Mainnet, Address::ZERO, 5);
+        assert_eq!(
+            nonces.0.keys().copied().collect::<Vec<_>>(),
+            vec![6, 7],
+            "counted and dropped nonces are forgotten"
+        );
+        assert_eq!(nonces.next(5), 5);
+    }
+
+    #[tokio::test]
+    async fn reservations_take_distinct_nonces() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+        let nonces = Nonces::default();
+        let chain = NamedChain::Mainnet;
+        for _ in 0..4 {
+            asserter.push_success(&U64::from(5));
+        }
+
+        let first = nonces
+            .reserve(chain, &provider, Address::ZERO)
+            .await
+            .unwrap();
+        let second = nonces
+            .reserve(chain, &provider, Address::ZERO)
+            .await
+            .unwrap();
+        assert_eq!((first.nonce(), second.nonce()), (5, 6));
+
+        // a released nonce is handed out again
+        drop(first);
+        let third = nonces
+            .reserve(chain, &provider, Address::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(third.nonce(), 5);
+
+        // a broadcast nonce stays taken once its reservation is gone
+        nonces.broadcast(chain, Address::ZERO, third.nonce());
+        nonces.broadcast(chain, Address::ZERO, second.nonce());
+        drop((second, third));
+        let fourth = nonces
+            .reserve(chain, &provider, Address::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(fourth.nonce(), 7);
+    }
+}
@@ -1,44 +1,62 @@
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, SocketAddr};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::hash_map::Entry;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
 
 use alloy::consensus::{EthereumTypedTransaction, TxEip4844Variant};
-use alloy::dyn_abi::TypedData;
-use alloy::primitives::{Address, Bytes};
-use alloy::providers::fillers::{
-    BlobGasFiller, ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller, TxFiller,
-};
+use alloy::dyn_abi::{DynSolType, TypedData};
+use alloy::primitives::{Address, B256, Bytes, TxHash, U64, keccak256};
+use alloy::providers::fillers::{BlobGasFiller, ChainIdFiller, FillProvider, JoinFill, TxFiller};
 use alloy::providers::{Provider, ProviderBuilder, RootProvider};
+use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
 use alloy::signers::Signature;
 use alloy_chains::NamedChain;
 use eyre::OptionExt;
 use futures::FutureExt;
 use futures::future::BoxFuture;
+use hyper::header::HeaderValue;
+use jsonrpsee::client_transport::ws::WsTransportClientBuilder;
+use jsonrpsee::core::client::{ReceivedMessage, TransportReceiverT, TransportSenderT};
+use jsonrpsee::core::server::BatchResponseBuilder;
 use jsonrpsee::core::traits::ToRpcParams;
-use jsonrpsee::server::middleware::rpc::{RpcServiceBuilder, RpcServiceT};
+use jsonrpsee::server::middleware::rpc::{
+    Batch, BatchEntry, Notification, RpcServiceBuilder, RpcServiceT,
+};
 use jsonrpsee::server::{
-    ServerHandle, StopHandle, TowerServiceBuilder, serve_with_graceful_shutdown, stop_channel,
+    BatchRequestConfig, HttpBody, HttpRequest, HttpResponse, ServerConfig, ServerHandle,
+    StopHandle, TowerServiceBuilder, serve_with_graceful_shutdown, stop_channel,
 };
-use jsonrpsee::types::{ErrorCode, ErrorObject, ErrorObjectOwned, Request};
-use jsonrpsee::{MethodResponse, RpcModule};
+use jsonrpsee::types::{Params, Request};
+use jsonrpsee::{ConnectionId, Extensions, MethodCallback, MethodResponse, RpcModule};
+use nexum_primitives::ConnectionState;
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::mpsc::{self, Receiver};
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch};
 use tower::Service;
+use tower::layer::util::Identity;
 use tracing::trace;
 use url::Url;
 
-use crate::namespaces::{eth, net, wallet, web3};
-
-#[derive(Clone, Debug, Default)]
-struct Metrics {
-    opened_ws_connections: Arc<AtomicUsize>,
-    closed_ws_connections: Arc<AtomicUsize>,
-    http_calls: Arc<AtomicUsize>,
-    success_http_calls: Arc<AtomicUsize>,
-}
+use crate::access::{AccessControl, Rejection};
+use crate::cache::{CacheStats, ResponseCache};
+use crate::error::WalletError;
+use crate::fees::{FeeFiller, FeeStrategies, FeeStrategy};
+use crate::metrics::{Metrics, PendingPrompt};
+use crate::namespaces::{eth, net, nexum, personal, wallet, web3};
+use crate::nonces::{Nonces, Reservation};
+use crate::simulation::Simulation;
+use crate::status::{Pending, Status};
+use crate::subscriptions::Subscriptions;
+use crate::upstream::Upstreams;
+use crate::webtransport::{WebTransportInfo, WebTransportServer};
 
 /// Request parameters
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -69,80 +87,731 @@ impl ToRpcParams for RequestParams {
     }
 }
 
+/// Maximum size of a single response, shared by the server config and the chain router in
+/// [`CallerContext`]
+const MAX_RESPONSE_BODY_SIZE: u32 = 10 * 1024 * 1024;
+/// Content type of the Prometheus text format served on `/metrics`
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Maximum size of a request
+const MAX_REQUEST_BODY_SIZE: u32 = 10 * 1024 * 1024;
+
+/// Default maximum number of calls in a JSON-RPC batch
+const DEFAULT_MAX_BATCH_LEN: u32 = 100;
+
+/// Methods that only act on the session, served even once its active chain has been removed
+const SESSION_METHODS: [&str; 2] = ["wallet_switchEthereumChain", "wallet_addEthereumChain"];
+
+/// Size of the in-memory pipe between a raw JSON-RPC connection and its WebSocket session
+const RAW_BUFFER_SIZE: usize = 64 * 1024;
+
 // It's possible to access the connection ID
 // by using the low-level API.
 #[derive(Clone)]
 pub struct CallerContext<S> {
     service: S,
+    sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    metrics: Metrics,
+    status: Status,
 }
 
-impl<'a, S> RpcServiceT<'a> for CallerContext<S>
+impl<S> RpcServiceT for CallerContext<S>
 where
-    S: RpcServiceT<'a> + Send + Sync + Clone + 'static,
+    S: RpcServiceT<
+            MethodResponse = MethodResponse,
+            BatchResponse = MethodResponse,
+            NotificationResponse = MethodResponse,
+        > + Send
+        + Sync
+        + Clone
+        + 'static,
 {
-    type Future = BoxFuture<'a, MethodResponse>;
+    type MethodResponse = MethodResponse;
+    type BatchResponse = MethodResponse;
+    type NotificationResponse = MethodResponse;
 
-    fn call(&self, req: Request<'a>) -> Self::Future {
+    fn call<'a>(&self, mut req: Request<'a>) -> impl Future<Output = MethodResponse> + Send + 'a {
         let service = self.service.clone();
+        let metrics = self.metrics.clone();
+        let status = self.status.clone();
 
         async move {
             trace!("Request: {:?}", req);
+            let started = Instant::now();
+            let chain = req.extensions().get::<Session>().map(Session::active_chain);
+
+            // route method calls to the session's active chain, which might have been switched
+            // since the connection was opened, or removed, in which case the session can still
+            // switch to another chain
+            let methods = req.extensions().get::<Session>().and_then(|session| {
+                session.active_methods().or_else(|| {
+                    SESSION_METHODS
+                        .contains(&req.method.as_ref())
+                        .then(|| session.chains().methods().any())
+                        .flatten()
+                })
+            });
+            let callback = methods
+                .as_ref()
+                .and_then(|methods| methods.method(&req.method).cloned());
+            let conn_id = req.extensions().get::<ConnectionId>().copied();
+            // arbitrary method names aren't recorded, so clients can't grow the metrics
+            let method = match callback {
+                Some(_) => req.method.to_string(),
+                None => "unsupported".to_string(),
+            };
+            if PROMPT_METHODS.contains(&method.as_str()) {
+                let origin = req
+                    .extensions()
+                    .get::<Session>()
+                    .and_then(|session| session.origin().cloned());
+                req.extensions_mut().insert(PromptRecorder {
+                    metrics: metrics.clone(),
+                    status,
+                    method: method.clone(),
+                    chain,
+                    origin,
+                });
+            }
+
+            let rp = match (callback, conn_id) {
+                // EIP-1193 unsupported method rather than the JSON-RPC method not found
+                (None, _) if methods.is_some() => MethodResponse::error(
+                    req.id,
+                    WalletError::UnsupportedMethod(req.method.to_string()),
+                ),
+                // the active chain has been removed while the session was open
+                (None, _) if chain.is_some() => MethodResponse::error(
+                    req.id,
+                    WalletError::ChainDisconnected(U64::from(
+                        chain.map_or(0, |chain| chain as u64),
+                    )),
+                ),
+                (Some(MethodCallback::Async(callback)), Some(conn_id)) => {
+                    let Request {
+                        id,
+                        params,
+                        extensions,
+                        ..
+                    } = req;
+                    let params = Params::new(params.as_ref().map(|p| p.get())).into_owned();
+                    callback(
+                        id.into_owned(),
+                        params,
+                        conn_id,
+                        MAX_RESPONSE_BODY_SIZE as usize,
+                        extensions,
+                    )
+                    .await
+                }
+                (Some(MethodCallback::Sync(callback)), _) => {
+                    let Request {
+                        id,
+                        params,
+                        extensions,
+                        ..
+                    } = req;
+                    let params = Params::new(params.as_ref().map(|p| p.get()));
+                    callback(id, params, MAX_RESPONSE_BODY_SIZE as usize, extensions)
+                }
+                // subscriptions need the connection's sink which only the inner service has
+                _ => service.call(req).await,
+            };
+
+            metrics.call(chain, &method, started.elapsed(), rp.is_error());
+            rp
+        }
+    }
+
+    /// Serves the calls of a batch in order, through [`Self::call`] so they're routed to the
+    /// session's active chain, once the user answered the batch's prompt
+    fn batch<'a>(&self, mut batch: Batch<'a>) -> impl Future<Output = MethodResponse> + Send + 'a {
+        let this = self.clone();
+        async move {
+            this.approve_batch(&mut batch).await;
+
+            let mut batch_rp =
+                BatchResponseBuilder::new_with_limit(MAX_RESPONSE_BODY_SIZE as usize);
+            let mut got_notification = false;
+            for entry in batch.into_iter() {
+                let rp = match entry {
+                    Ok(BatchEntry::Call(req)) => this.call(req).await,
+                    Ok(BatchEntry::Notification(notification)) => {
+                        got_notification = true;
+                        this.service.notification(notification).await;
+                        continue;
+                    }
+                    Err(err) => {
+                        let (err, id) = err.into_parts();
+                        MethodResponse::error(id, err)
+                    }
+                };
+                if let Err(err) = batch_rp.append(rp) {
+                    return err;
+                }
+            }
+            // a batch of notifications only isn't answered, an empty batch is invalid
+            if batch_rp.is_empty() && got_notification {
+                MethodResponse::notification()
+            } else {
+                MethodResponse::from_batch(batch_rp.finish())
+            }
+        }
+    }
+
+    fn notification<'a>(
+        &self,
+        notification: Notification<'a>,
+    ) -> impl Future<Output = MethodResponse> + Send + 'a {
+        self.service.notification(notification)
+    }
+}
+
+impl<S> CallerContext<S> {
+    /// Prompts the user once for all the calls of a JSON-RPC batch that may prompt, so clients
+    /// sending several transactions at once don't open a prompt per transaction. The answer is
+    /// inserted into the extensions of these calls only, so it can't approve any other call.
+    async fn approve_batch(&self, batch: &mut Batch<'_>) {
+        let mut reqs = batch
+            .iter_mut()
+            .filter_map(|entry| match entry {
+                Ok(BatchEntry::Call(req)) if PROMPT_METHODS.contains(&req.method.as_ref()) => {
+                    Some(req)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if reqs.len() < 2 {
+            return;
+        }
+        let Some(session) = reqs[0].extensions().get::<Session>().cloned() else {
+            return;
+        };
+
+        let origin = session.origin().cloned();
+        // the calls of origins without access to an account fail on their own
+        let accounts = match make_interactive_request(
+            self.sender.clone(),
+            InteractiveRequest::EthAccounts(origin.clone()),
+        )
+        .await
+        {
+            Ok(InteractiveResponse::EthAccounts(accounts)) if !accounts.is_empty() => accounts,
+            _ => return,
+        };
+
+        let mut calls = reqs
+            .iter()
+            .map(|req| BatchCall {
+                method: req.method.to_string(),
+                params: req
+                    .params
+                    .as_ref()
+                    .and_then(|params| serde_json::from_str(params.get()).ok())
+                    .unwrap_or_default(),
+                transaction: None,
+            })
+            .collect::<Vec<_>>();
+        // the transactions are shown as they'll be signed
+        let chain = session.active_chain();
+        if let Some(ctx) = session.chains().context(chain) {
+            for call in &mut calls {
+                if !TRANSACTION_METHODS.contains(&call.method.as_str()) {
+                    continue;
+                }
+                call.transaction = eth::prepare_batch_transaction(&ctx, &accounts, &call.params)
+                    .await
+                    .inspect_err(
+                        |err| tracing::debug!(?err, %chain, "failed to prepare batch transaction"),
+                    )
+                    .ok();
+            }
+        }
+
+        let prompts = PromptRecorder {
+            metrics: self.metrics.clone(),
+            status: self.status.clone(),
+            method: "batch".to_string(),
+            chain: Some(chain),
+            origin: origin.clone(),
+        };
+        let pending = prompts.record();
+        let approval = make_interactive_request(
+            self.sender.clone(),
+            InteractiveRequest::ApproveBatch(origin, calls.clone()),
+        )
+        .await;
+        drop(pending);
+        let approved = match approval {
+            Ok(InteractiveResponse::ApproveBatch(approved)) => approved,
+            res => {
+                tracing::warn!(?res, "failed to approve batch");
+                return;
+            }
+        };
+        // the transactions of rejected calls are dropped, along with their nonces
+        for (req, call) in reqs.iter_mut().zip(calls) {
+            req.extensions_mut().insert(BatchApproval {
+                approved,
+                transaction: call.transaction.filter(|_| approved),
+            });
+        }
+    }
+}
+
+/// RPC methods of every connected chain. The map is shared between the server and all the
+/// sessions, so chains added at runtime are visible to connections that are already open.
+#[derive(Clone, Debug, Default)]
+pub struct ChainMethods(Arc<RwLock<HashMap<NamedChain, RpcModule<GlobalRpcContextT>>>>);
+
+impl ChainMethods {
+    pub fn get(&self, chain: &NamedChain) -> Option<RpcModule<GlobalRpcContextT>> {
+        self.r_methods().get(chain).cloned()
+    }
+
+    pub fn contains(&self, chain: &NamedChain) -> bool {
+        self.r_methods().contains_key(chain)
+    }
+
+    pub fn insert(&self, chain: NamedChain, methods: RpcModule<GlobalRpcContextT>) {
+        self.w_methods().insert(chain, methods);
+    }
+
+    pub fn remove(&self, chain: &NamedChain) -> Option<RpcModule<GlobalRpcContextT>> {
+        self.w_methods().remove(chain)
+    }
+
+    pub fn chains(&self) -> Vec<NamedChain> {
+        self.r_methods().keys().copied().collect()
+    }
+
+    /// Methods of any of the chains, for calls that don't depend on the chain
+    pub fn any(&self) -> Option<RpcModule<GlobalRpcContextT>> {
+        self.r_methods().values().next().cloned()
+    }
+
+    fn r_methods(&self) -> RwLockReadGuard<'_, HashMap<NamedChain, RpcModule<GlobalRpcContextT>>> {
+        self.0
+            .read()
+            .expect("failed to get read lock on chain methods")
+    }
+
+    fn w_methods(&self) -> RwLockWriteGuard<'_, HashMap<NamedChain, RpcModule<GlobalRpcContextT>>> {
+        self.0
+            .write()
+            .expect("failed to get write lock on chain methods")
+    }
+}
+
+/// State of a single client session (a WebSocket connection or an HTTP request). It is
+/// inserted into the request extensions so method handlers can read and update it.
+#[derive(Clone, Debug)]
+pub struct Session {
+    chains: Chains,
+    active_chain: Arc<watch::Sender<NamedChain>>,
+    origin: Option<Url>,
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Session {
+    pub fn new(chains: Chains, active_chain: NamedChain, origin: Option<Url>) -> Self {
+        Self {
+            chains,
+            active_chain: Arc::new(watch::Sender::new(active_chain)),
+            origin,
+            closed: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    /// Origin of the web page that opened the session. Clients that aren't browsers, such as
+    /// scripts and local tools, don't send an origin.
+    pub fn origin(&self) -> Option<&Url> {
+        self.origin.as_ref()
+    }
+
+    pub fn chains(&self) -> &Chains {
+        &self.chains
+    }
+
+    pub fn active_chain(&self) -> NamedChain {
+        *self.active_chain.borrow()
+    }
+
+    /// Switches the session to the given chain. Returns false if the chain isn't configured.
+    pub fn switch_chain(&self, chain: NamedChain) -> bool {
+        if !self.chains.methods().contains(&chain) {
+            return false;
+        }
+        self.active_chain.send_replace(chain);
+        true
+    }
+
+    /// Resolves once the session switched away from the chain
+    pub async fn switched_from(&self, chain: NamedChain) {
+        // the sender lives as long as the session, so waiting can't fail
+        self.active_chain
+            .subscribe()
+            .wait_for(|active| *active != chain)
+            .await
+            .ok();
+    }
+
+    /// Marks the session as closed, e.g. once the WebSocket connection is gone
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// Resolves once the session is closed
+    pub async fn closed(&self) {
+        // the sender lives as long as the session, so waiting can't fail
+        self.closed
+            .subscribe()
+            .wait_for(|closed| *closed)
+            .await
+            .ok();
+    }
+
+    fn active_methods(&self) -> Option<RpcModule<GlobalRpcContextT>> {
+        self.chains.methods().get(&self.active_chain())
+    }
+}
+
+/// EIP-5792 call batch sent with `wallet_sendCalls`
+#[derive(Clone, Debug)]
+pub struct CallBatch {
+    pub chain: NamedChain,
+    /// Number of calls in the batch, sending stops at the first call that fails
+    pub calls: usize,
+    pub tx_hashes: Vec<TxHash>,
+    pub receipts: HashMap<TxHash, TransactionReceipt>,
+    /// The receipt of a call couldn't be fetched, e.g. its transaction wasn't included in time
+    pub failed: bool,
+}
+
+/// Call batches by their id. Shared by all the chains since the status of a batch can be
+/// queried after switching to another chain.
+#[derive(Clone, Debug, Default)]
+pub struct CallBatches(Arc<RwLock<HashMap<String, CallBatch>>>);
+
+impl CallBatches {
+    pub fn get(&self, id: &str) -> Option<CallBatch> {
+        self.r_batches().get(id).cloned()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.r_batches().contains_key(id)
+    }
+
+    pub fn insert(&self, id: String, batch: CallBatch) {
+        self.w_batches().insert(id, batch);
+    }
+
+    /// Inserts the batch unless its id is taken. Returns false if it is.
+    pub fn try_insert(&self, id: String, batch: CallBatch) -> bool {
+        match self.w_batches().entry(id) {
+            Entry::Vacant(entry) => {
+                entry.insert(batch);
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.w_batches().remove(id);
+    }
+
+    /// Marks the batch as failed, its remaining calls are given up on
+    pub fn fail(&self, id: &str) {
+        if let Some(batch) = self.w_batches().get_mut(id) {
+            batch.failed = true;
+        }
+    }
 
-            service.call(req).await
+    pub fn insert_receipt(&self, id: &str, receipt: TransactionReceipt) {
+        if let Some(batch) = self.w_batches().get_mut(id) {
+            batch.receipts.insert(receipt.transaction_hash, receipt);
         }
-        .boxed()
+    }
+
+    fn r_batches(&self) -> RwLockReadGuard<'_, HashMap<String, CallBatch>> {
+        self.0
+            .read()
+            .expect("failed to get read lock on call batches")
+    }
+
+    fn w_batches(&self) -> RwLockWriteGuard<'_, HashMap<String, CallBatch>> {
+        self.0
+            .write()
+            .expect("failed to get write lock on call batches")
     }
 }
 
 /// Requests that need some interactive or external input to compute the response
 pub enum InteractiveRequest {
-    EthRequestAccounts,
-    EthAccounts,
-    SignTransaction(Box<EthereumTypedTransaction<TxEip4844Variant>>),
+    /// Request access to the accounts for the origin, prompting the user if not yet granted
+    EthRequestAccounts(Option<Url>),
+    /// Accounts the origin has been granted access to
+    EthAccounts(Option<Url>),
+    /// Whether the origin may use the account to sign. Clients without an origin may use any
+    /// account of the wallet.
+    AuthorizeSigner(Option<Url>, Address),
+    /// Sign the transaction for the origin, along with the outcome of its simulation unless it
+    /// has already been approved
+    SignTransaction(
+        Option<Url>,
+        Address,
+        Box<EthereumTypedTransaction<TxEip4844Variant>>,
+        Option<Simulation>,
+    ),
     EthSign(Address, Bytes),
     EthSignTypedData(Address, Box<TypedData>),
+    /// Sign the legacy typed data of `eth_signTypedData`, the response is an
+    /// [`InteractiveResponse::EthSignTypedData`]
+    EthSignLegacyTypedData(Address, Box<LegacyTypedData>),
+    /// Approve adding a new chain with the given upstream rpc urls
+    WalletAddEthereumChain(NamedChain, Vec<Url>),
+    /// All the accounts the origin has been granted access to, active or not
+    WalletGetPermissions(Option<Url>),
+    /// Track the ERC-20 token, its metadata has already been checked against the contract
+    WalletWatchAsset(NamedChain, Address, TokenMetadata),
+    /// Approve sending the whole batch of calls from the account
+    WalletSendCalls(NamedChain, Address, Vec<TransactionRequest>),
+    /// Approve the interactive calls of a JSON-RPC batch with a single prompt
+    ApproveBatch(Option<Url>, Vec<BatchCall>),
+    /// Fulfil the request without prompting, the user has already approved it as part of a
+    /// JSON-RPC batch or an EIP-5792 call batch
+    Approved(Box<InteractiveRequest>),
+}
+
+impl InteractiveRequest {
+    /// Wraps the request in [`InteractiveRequest::Approved`] if the user has approved it
+    pub fn approved_if(self, approved: bool) -> Self {
+        if approved {
+            Self::Approved(Box::new(self))
+        } else {
+            self
+        }
+    }
 }
 
 /// Responses for the interactive requests
 #[derive(Debug)]
 pub enum InteractiveResponse {
-    EthRequestAccounts(Vec<Address>),
+    /// `None` if the user rejected the request
+    EthRequestAccounts(Option<Vec<Address>>),
     EthAccounts(Vec<Address>),
-    SignTransaction(Result<Signature, Box<dyn std::error::Error + Send + Sync>>),
-    EthSign(Result<Signature, Box<dyn std::error::Error + Send + Sync>>),
-    EthSignTypedData(Result<Signature, Box<dyn std::error::Error + Send + Sync>>),
+    AuthorizeSigner(bool),
+    /// The transaction the user approved, with the gas, fees and nonce they may have edited, and
+    /// its signature
+    SignTransaction(
+        Result<(Box<EthereumTypedTransaction<TxEip4844Variant>>, Signature), WalletError>,
+    ),
+    EthSign(Result<Signature, WalletError>),
+    EthSignTypedData(Result<Signature, WalletError>),
+    /// Whether the user approved adding the chain
+    WalletAddEthereumChain(bool),
+    WalletGetPermissions(Vec<Address>),
+    /// Whether the token is tracked
+    WalletWatchAsset(bool),
+    /// Whether the user approved the batch
+    WalletSendCalls(bool),
+    /// Whether the user approved the interactive calls of the JSON-RPC batch
+    ApproveBatch(bool),
+}
+
+/// Interactive call of a JSON-RPC batch, as shown to the user
+#[derive(Clone, Debug)]
+pub struct BatchCall {
+    pub method: String,
+    pub params: serde_json::Value,
+    /// Transaction of `eth_sendTransaction` and `eth_signTransaction` calls, `None` if it
+    /// couldn't be filled
+    pub transaction: Option<BatchTransaction>,
+}
+
+/// Transaction of a JSON-RPC batch call, filled and simulated for the user to review it. Once the
+/// batch is approved, the call signs it as it was shown.
+#[derive(Clone, Debug)]
+pub struct BatchTransaction {
+    pub from: Address,
+    pub tx: Box<EthereumTypedTransaction<TxEip4844Variant>>,
+    pub simulation: Simulation,
+    /// Nonce held from the prompt until the transaction is signed, `None` if the client set it
+    pub(crate) _reservation: Option<Arc<Reservation>>,
+}
+
+/// Methods that may prompt the user, their calls are pending until the user answers. When a
+/// JSON-RPC batch has several of them, they are approved with a single prompt.
+const PROMPT_METHODS: [&str; 12] = [
+    "eth_requestAccounts",
+    "eth_sendTransaction",
+    "eth_signTransaction",
+    "eth_sign",
+    "personal_sign",
+    "eth_signTypedData",
+    "eth_signTypedData_v3",
+    "eth_signTypedData_v4",
+    "wallet_sendCalls",
+    "wallet_addEthereumChain",
+    "wallet_watchAsset",
+    "wallet_requestPermissions",
+];
+
+/// Methods of the batch calls whose transaction is filled and simulated before prompting
+const TRANSACTION_METHODS: [&str; 2] = ["eth_sendTransaction", "eth_signTransaction"];
+
+/// Answer of the user to the prompt of a JSON-RPC batch, inserted into the extensions of the
+/// batch's calls by [`CallerContext`]
+#[derive(Clone, Debug)]
+struct BatchApproval {
+    approved: bool,
+    transaction: Option<BatchTransaction>,
+}
+
+/// Returns whether the call is part of a JSON-RPC batch the user approved, so it is fulfilled
+/// without prompting. Fails if the user rejected the batch.
+pub fn batch_approved(ext: &Extensions) -> Result<bool, WalletError> {
+    match ext.get::<BatchApproval>() {
+        Some(BatchApproval { approved: true, .. }) => Ok(true),
+        Some(BatchApproval {
+            approved: false, ..
+        }) => Err(WalletError::UserRejected),
+        None => Ok(false),
+    }
+}
+
+/// Returns the transaction of the call as the user approved it in a JSON-RPC batch
+pub fn batch_transaction(ext: &Extensions) -> Option<&BatchTransaction> {
+    ext.get::<BatchApproval>()
+        .and_then(|approval| approval.transaction.as_ref())
+}
+
+/// Metadata of an ERC-20 token
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Typed data of the legacy `eth_signTypedData` (v1), a flat list of fields that predates
+/// EIP-712
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LegacyTypedData(pub Vec<LegacyTypedDataField>);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LegacyTypedDataField {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+impl LegacyTypedData {
+    /// Hash that gets signed, without any prefix:
+    /// `keccak256(keccak256(packed schema) ‖ keccak256(packed values))`, where the schema
+    /// is the `"<type> <name>"` string of every field
+    pub fn signature_hash(&self) -> eyre::Result<B256> {
+        let schema = self
+            .0
+            .iter()
+            .map(|field| format!("{} {}", field.type_, field.name))
+            .collect::<String>();
+        let values = self
+            .0
+            .iter()
+            .map(|field| {
+                Ok(DynSolType::parse(&field.type_)?
+                    .coerce_json(&field.value)?
+                    .abi_encode_packed())
+            })
+            .collect::<eyre::Result<Vec<_>>>()?
+            .concat();
+        Ok(keccak256(
+            [keccak256(schema.as_bytes()), keccak256(values)].concat(),
+        ))
+    }
+}
+
+/// Records the prompts shown to the user for a call as pending, and the time the user takes to
+/// answer them. It's inserted into the extensions of the calls that may prompt.
+#[derive(Clone, Debug)]
+pub struct PromptRecorder {
+    metrics: Metrics,
+    status: Status,
+    method: String,
+    chain: Option<NamedChain>,
+    origin: Option<Url>,
+}
+
+impl PromptRecorder {
+    /// Records a prompt as pending until the returned guards are dropped
+    pub fn record(&self) -> (PendingPrompt, Pending) {
+        (
+            self.metrics.prompt(&self.method),
+            self.status
+                .pending(&self.method, self.chain, self.origin.clone()),
+        )
+    }
+}
+
+/// Prompts the user with the request, the call is recorded as pending until they answer it.
+/// Requests the user has already approved are fulfilled right away.
+pub async fn prompt_user(
+    sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    ext: &Extensions,
+    request: InteractiveRequest,
+) -> Result<InteractiveResponse, WalletError> {
+    let _pending = match request {
+        InteractiveRequest::Approved(_) => None,
+        _ => ext.get::<PromptRecorder>().map(PromptRecorder::record),
+    };
+    make_interactive_request(sender, request).await
 }
 
 pub async fn make_interactive_request(
     sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
     request: InteractiveRequest,
-) -> eyre::Result<InteractiveResponse> {
+) -> Result<InteractiveResponse, WalletError> {
     let (res_sender, receiver) = oneshot::channel::<InteractiveResponse>();
-    sender.send((request, res_sender)).await?;
-    Ok(receiver.await?)
+    // the UI is gone, nothing can be approved anymore
+    sender
+        .send((request, res_sender))
+        .await
+        .map_err(|_| WalletError::Disconnected)?;
+    receiver.await.map_err(|_| WalletError::Disconnected)
 }
 
 #[derive(Clone, Debug)]
 pub struct GlobalRpcContext<F: TxFiller, P: Provider> {
+    pub chain: NamedChain,
     pub sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    pub call_batches: CallBatches,
+    pub subscriptions: Subscriptions,
+    pub upstreams: Upstreams,
+    pub cache: ResponseCache,
     pub provider: Arc<FillProvider<F, P>>,
+    pub status: Status,
+    /// Nonces of the transactions sent through the wallet, shared by every chain and connection
+    pub nonces: Nonces,
 }
 
-pub fn json_rpc_internal_error<E>(err: E) -> ErrorObjectOwned
-where
-    E: std::fmt::Debug,
-{
-    ErrorObject::owned(
-        ErrorCode::InternalError.code(),
-        format!("{err:?}"),
-        None::<()>,
-    )
+/// Returns the session the request was made in
+pub fn session(ext: &Extensions) -> Result<&Session, WalletError> {
+    ext.get::<Session>()
+        .ok_or_else(|| WalletError::Internal("request has no session".to_string()))
 }
 
 pub struct RpcServerBuilder {
-    rpcs: HashMap<NamedChain, Url>,
+    rpcs: HashMap<NamedChain, Vec<Url>>,
+    fee_strategies: HashMap<NamedChain, FeeStrategy>,
     port: u16,
-    host: Ipv4Addr,
+    hosts: Vec<IpAddr>,
+    ipc_path: Option<PathBuf>,
+    webtransport_port: Option<u16>,
+    batch_config: BatchRequestConfig,
+    access: AccessControl,
 }
 
 impl Default for RpcServerBuilder {
@@ -155,8 +824,13 @@ impl RpcServerBuilder {
     pub fn new() -> Self {
         Self {
             rpcs: HashMap::new(),
+            fee_strategies: HashMap::new(),
             port: 1248,
-            host: Ipv4Addr::LOCALHOST,
+            hosts: vec![Ipv4Addr::LOCALHOST.into()],
+            ipc_path: None,
+            webtransport_port: None,
+            batch_config: BatchRequestConfig::Limit(DEFAULT_MAX_BATCH_LEN),
+            access: AccessControl::default(),
         }
     }
 
@@ -165,18 +839,98 @@ impl RpcServerBuilder {
         self
     }
 
-    pub fn host(mut self, host: Ipv4Addr) -> Self {
-        self.host = host;
+    /// Listens on the address only, IPv4 or IPv6
+    pub fn host(mut self, host: impl Into<IpAddr>) -> Self {
+        self.hosts = vec![host.into()];
+        self
+    }
+
+    /// Listens on each of the addresses, e.g. on both `127.0.0.1` and `::1`
+    pub fn hosts(mut self, hosts: Vec<IpAddr>) -> Self {
+        self.hosts = hosts;
+        self
+    }
+
+    /// Also listens for raw JSON-RPC on a Unix domain socket at the path, e.g. for Foundry's
+    /// `--rpc-url /path/to/nexum.ipc`
+    pub fn ipc_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ipc_path = Some(path.into());
+        self
+    }
+
+    /// Also serves WebTransport over HTTP/3 on the UDP port, with a self-signed certificate whose
+    /// hash is served on `/webtransport`
+    pub fn webtransport_port(mut self, port: u16) -> Self {
+        self.webtransport_port = Some(port);
         self
     }
 
+    /// Adds an upstream rpc for the chain, upstreams added first are preferred
     pub fn chain(mut self, chain: NamedChain, rpc: Url) -> Self {
-        self.rpcs.insert(chain, rpc);
+        self.rpcs.entry(chain).or_default().push(rpc);
+        self
+    }
+
+    /// Sets the upstream rpcs of the chain in order of preference, replacing the ones added
+    /// before
+    pub fn upstreams(mut self, chain: NamedChain, rpcs: Vec<Url>) -> Self {
+        self.rpcs.insert(chain, rpcs);
+        self
+    }
+
+    /// Prices the transactions of the chain with the strategy instead of the default one
+    pub fn fee_strategy(mut self, chain: NamedChain, strategy: FeeStrategy) -> Self {
+        self.fee_strategies.insert(chain, strategy);
+        self
+    }
+
+    /// Limits the number of calls in a JSON-RPC batch, `0` disables batches
+    pub fn max_batch_len(mut self, len: u32) -> Self {
+        self.batch_config = match len {
+            0 => BatchRequestConfig::Disabled,
+            len => BatchRequestConfig::Limit(len),
+        };
+        self
+    }
+
+    /// Allows JSON-RPC batches with any number of calls
+    pub fn unlimited_batches(mut self) -> Self {
+        self.batch_config = BatchRequestConfig::Unlimited;
+        self
+    }
+
+    /// Host names the clients may reach the server by, in addition to the address it listens on
+    pub fn allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.access.allowed_hosts = hosts;
+        self
+    }
+
+    /// Browser origins allowed to connect, `*` allows any
+    pub fn allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.access.allowed_origins = origins;
+        self
+    }
+
+    /// Requires the clients to authenticate with the bearer token
+    pub fn token(mut self, token: String) -> Self {
+        self.access.token = Some(token);
         self
     }
 
     pub async fn build(self) -> RpcServer {
-        RpcServer::new(self.rpcs, self.port, self.host).await
+        let server = RpcServer::new(
+            self.rpcs,
+            self.port,
+            self.hosts,
+            self.ipc_path,
+            self.webtransport_port,
+            self.batch_config,
+            self.access,
+        )
+        .await;
+        // the strategies are read as the transactions are filled
+        server.chains.fee_strategies.sync(self.fee_strategies);
+        server
     }
 }
 
@@ -193,79 +947,354 @@ pub fn chain_id_or_name_to_named_chain(chain: &str) -> eyre::Result<NamedChain>
 
 pub type ProviderFillers = JoinFill<
     alloy::providers::Identity,
-    JoinFill<GasFiller, JoinFill<BlobGasFiller, JoinFill<NonceFiller, ChainIdFiller>>>,
+    JoinFill<FeeFiller, JoinFill<BlobGasFiller, ChainIdFiller>>,
 >;
 pub type ProviderWithFillers = FillProvider<ProviderFillers, RootProvider>;
 pub type GlobalRpcContextT = GlobalRpcContext<ProviderFillers, RootProvider>;
 
+fn chain_methods(global_ctx: GlobalRpcContextT) -> eyre::Result<RpcModule<GlobalRpcContextT>> {
+    let mut methods = RpcModule::new(global_ctx.clone());
+    methods.merge(eth::init(global_ctx.clone())?)?;
+    methods.merge(net::init(global_ctx.clone())?)?;
+    methods.merge(nexum::init(global_ctx.clone())?)?;
+    methods.merge(personal::init(global_ctx.clone())?)?;
+    methods.merge(web3::init(global_ctx.clone())?)?;
+    methods.merge(wallet::init(global_ctx.clone())?)?;
+    Ok(methods)
+}
+
+/// Upstreams, response cache and subscriptions of a chain served by the server
+#[derive(Clone, Debug)]
+struct ServedChain {
+    rpcs: Vec<Url>,
+    context: GlobalRpcContextT,
+    /// Added by a client with `wallet_addEthereumChain`, such chains are kept when the chains
+    /// are synced without them
+    added: bool,
+}
+
+/// Chains served by the server. The handle is shared with the server, so chains added, removed or
+/// pointed to other upstreams while the server runs are served right away, including to the
+/// sessions that are already open, which route each call to their active chain.
+#[derive(Clone, Debug)]
+pub struct Chains {
+    methods: ChainMethods,
+    served: Arc<RwLock<HashMap<NamedChain, ServedChain>>>,
+    sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    call_batches: CallBatches,
+    status: Status,
+    fee_strategies: FeeStrategies,
+    nonces: Nonces,
+    /// Updates connect to the upstreams, they're applied one at a time
+    updating: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Chains {
+    fn new(
+        sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+        call_batches: CallBatches,
+        status: Status,
+    ) -> Self {
+        Self {
+            methods: Default::default(),
+            served: Default::default(),
+            sender,
+            call_batches,
+            status,
+            fee_strategies: Default::default(),
+            nonces: Default::default(),
+            updating: Default::default(),
+        }
+    }
+
+    pub fn methods(&self) -> &ChainMethods {
+        &self.methods
+    }
+
+    /// Handle to the fee strategy of each chain, strategies changed while the server runs price
+    /// the next transactions of the chains
+    pub fn fee_strategies(&self) -> &FeeStrategies {
+        &self.fee_strategies
+    }
+
+    /// Context the methods of the chain are served with, `None` if it isn't served
+    pub(crate) fn context(&self, chain: NamedChain) -> Option<GlobalRpcContextT> {
+        self.r_served()
+            .get(&chain)
+            .map(|served| served.context.clone())
+    }
+
+    /// Provider of the upstreams of the chain, `None` if it isn't served
+    pub fn provider(&self, chain: NamedChain) -> Option<RootProvider> {
+        self.r_served()
+            .get(&chain)
+            .map(|served| served.context.upstreams.provider())
+    }
+
+    /// Upstream rpcs of each chain, in order of preference
+    pub fn rpcs(&self) -> HashMap<NamedChain, Vec<Url>> {
+        self.r_served()
+            .iter()
+            .map(|(chain, served)| (*chain, served.rpcs.clone()))
+            .collect()
+    }
+
+    /// Connection state of each chain by chain id, a chain is connected while any of its
+    /// upstreams is healthy
+    pub fn states(&self) -> Vec<(NamedChain, ConnectionState)> {
+        let mut states = self
+            .r_served()
+            .iter()
+            .map(|(chain, served)| {
+                let state = if served.context.upstreams.is_healthy() {
+                    ConnectionState::Connected
+                } else {
+                    ConnectionState::Disconnected
+                };
+                (*chain, state)
+            })
+            .collect::<Vec<_>>();
+        states.sort_by_key(|(chain, _)| *chain as u64);
+        states
+    }
+
+    /// Counters of the response cache of each chain
+    pub fn cache_stats(&self) -> HashMap<NamedChain, CacheStats> {
+        self.r_served()
+            .iter()
+            .map(|(chain, served)| (*chain, served.context.cache.stats()))
+            .collect()
+    }
+
+    /// Serves the chain from the upstreams, replacing the upstreams it's served from. The
+    /// subscriptions already made on the chain keep their upstreams until they're dropped.
+    pub async fn set(&self, chain: NamedChain, rpcs: Vec<Url>) -> eyre::Result<()> {
+        let _updating = self.updating.lock().await;
+        self.connect(chain, rpcs, false).await
+    }
+
+    /// Serves the chain a client added, which is kept when the chains are synced until they're
+    /// synced with the same upstreams, e.g. once the chain has been saved to the config. Fails if
+    /// none of the upstreams serves the chain.
+    pub async fn add(&self, chain: NamedChain, rpcs: Vec<Url>) -> eyre::Result<()> {
+        let _updating = self.updating.lock().await;
+        // the chain may have been synced already
+        if self
+            .r_served()
+            .get(&chain)
+            .is_some_and(|served| served.rpcs == rpcs)
+        {
+            return Ok(());
+        }
+        let upstreams = Upstreams::connect(chain, &rpcs).await;
+        if !upstreams.is_healthy() {
+            eyre::bail!("no rpc serving chain {chain} could be reached");
+        }
+        self.serve(chain, rpcs, upstreams, true)
+    }
+
+    /// Stops serving the chain. Returns false if it wasn't served.
+    pub async fn remove(&self, chain: NamedChain) -> bool {
+        let _updating = self.updating.lock().await;
+        self.disconnect(chain)
+    }
+
+    /// Serves the chains from the upstreams: chains that are missing are removed, new chains are
+    /// added and chains whose upstreams changed are reconnected. Chains whose upstreams didn't
+    /// change keep their connections, caches and subscriptions, and chains added by clients are
+    /// kept.
+    pub async fn sync(&self, rpcs: HashMap<NamedChain, Vec<Url>>) {
+        let _updating = self.updating.lock().await;
+        let current = {
+            let mut served = self.w_served();
+            // chains added by clients are kept as they are once they're synced, so they're
+            // removed with the other chains from then on
+            for (chain, served) in served.iter_mut() {
+                served.added &= rpcs.get(chain) != Some(&served.rpcs);
+            }
+            served
+                .iter()
+                .filter(|(_, served)| !served.added)
+                .map(|(chain, served)| (*chain, served.rpcs.clone()))
+                .collect::<HashMap<_, _>>()
+        };
+        for chain in current.keys().filter(|chain| !rpcs.contains_key(chain)) {
+            self.disconnect(*chain);
+        }
+        let changed = rpcs
+            .into_iter()
+            .filter(|(chain, rpcs)| current.get(chain) != Some(rpcs))
+            .map(|(chain, rpcs)| (chain, rpcs, false));
+        self.connect_all(changed).await;
+    }
+
+    /// Reconnects the upstreams of every chain
+    async fn reconnect(&self) {
+        let _updating = self.updating.lock().await;
+        let served = self
+            .r_served()
+            .iter()
+            .map(|(chain, served)| (*chain, served.rpcs.clone(), served.added))
+            .collect::<Vec<_>>();
+        self.connect_all(served).await;
+    }
+
+    async fn connect_all(&self, rpcs: impl IntoIterator<Item = (NamedChain, Vec<Url>, bool)>) {
+        futures::future::join_all(rpcs.into_iter().map(|(chain, rpcs, added)| async move {
+            self.connect(chain, rpcs, added)
+                .await
+                .inspect_err(
+                    |err| tracing::warn!(%chain, ?err, "error initializing chain rpc module"),
+                )
+                .ok();
+        }))
+        .await;
+    }
+
+    async fn connect(&self, chain: NamedChain, rpcs: Vec<Url>, added: bool) -> eyre::Result<()> {
+        // chains whose upstreams are all down are kept, they're reconnected in the background
+        let upstreams = Upstreams::connect(chain, &rpcs).await;
+        self.serve(chain, rpcs, upstreams, added)
+    }
+
+    fn serve(
+        &self,
+        chain: NamedChain,
+        rpcs: Vec<Url>,
+        upstreams: Upstreams,
+        added: bool,
+    ) -> eyre::Result<()> {
+        let fillers = JoinFill::new(
+            FeeFiller::new(chain, self.fee_strategies.clone()),
+            JoinFill::new(BlobGasFiller::default(), ChainIdFiller::default()),
+        );
+        let provider = ProviderBuilder::default()
+            .filler(fillers)
+            .connect_provider(upstreams.provider());
+        let context = GlobalRpcContext {
+            chain,
+            sender: self.sender.clone(),
+            call_batches: self.call_batches.clone(),
+            subscriptions: Subscriptions::default(),
+            upstreams,
+            cache: ResponseCache::default(),
+            provider: Arc::new(provider),
+            status: self.status.clone(),
+            nonces: self.nonces.clone(),
+        };
+        let methods = chain_methods(context.clone())?;
+        self.w_served().insert(
+            chain,
+            ServedChain {
+                rpcs,
+                context,
+                added,
+            },
+        );
+        self.methods.insert(chain, methods);
+        self.status.changed();
+        tracing::info!(%chain, "serving chain");
+        Ok(())
+    }
+
+    fn disconnect(&self, chain: NamedChain) -> bool {
+        self.w_served().remove(&chain);
+        let removed = self.methods.remove(&chain).is_some();
+        if removed {
+            self.status.changed();
+            tracing::info!(%chain, "stopped serving chain");
+        }
+        removed
+    }
+
+    fn r_served(&self) -> RwLockReadGuard<'_, HashMap<NamedChain, ServedChain>> {
+        self.served
+            .read()
+            .expect("failed to get read lock on served chains")
+    }
+
+    fn w_served(&self) -> RwLockWriteGuard<'_, HashMap<NamedChain, ServedChain>> {
+        self.served
+            .write()
+            .expect("failed to get write lock on served chains")
+    }
+}
+
 pub struct RpcServer {
-    rpc_urls: HashMap<NamedChain, Url>,
-    providers: HashMap<NamedChain, ProviderWithFillers>,
+    chains: Chains,
+    metrics: Metrics,
+    status: Status,
     port: u16,
-    host: Ipv4Addr,
+    hosts: Vec<IpAddr>,
+    ipc_path: Option<PathBuf>,
+    webtransport_port: Option<u16>,
+    webtransport: Option<WebTransportInfo>,
+    batch_config: BatchRequestConfig,
+    access: AccessControl,
     req_receiver:
         Option<mpsc::Receiver<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>>,
     req_sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
-    chain_methods_map: Arc<HashMap<NamedChain, RpcModule<GlobalRpcContextT>>>,
 }
 
 impl RpcServer {
-    pub async fn new(rpcs: HashMap<NamedChain, Url>, port: u16, host: Ipv4Addr) -> Self {
+    pub async fn new(
+        rpcs: HashMap<NamedChain, Vec<Url>>,
+        port: u16,
+        hosts: Vec<IpAddr>,
+        ipc_path: Option<PathBuf>,
+        webtransport_port: Option<u16>,
+        batch_config: BatchRequestConfig,
+        access: AccessControl,
+    ) -> Self {
         let (req_sender, req_receiver) = mpsc::channel(100);
+        let status = Status::default();
 
-        let mut this = Self {
-            rpc_urls: rpcs,
-            providers: Default::default(),
+        let this = Self {
+            chains: Chains::new(req_sender.clone(), CallBatches::default(), status.clone()),
+            metrics: Default::default(),
+            status,
             port,
-            host,
+            hosts,
+            ipc_path,
+            webtransport_port,
+            webtransport: None,
+            batch_config,
+            access,
             req_receiver: Some(req_receiver),
-            req_sender: req_sender.clone(),
-            chain_methods_map: Default::default(),
+            req_sender,
         };
-        this.reinit().await;
+        this.chains.sync(rpcs).await;
         this
     }
 
-    pub async fn reinit(&mut self) {
-        let providers = futures::future::join_all(self.rpc_urls.iter().map(|(k, v)| async {
-            RootProvider::connect(v.to_string().as_str())
-                .await
-                .map(|p| {
-                    let provider = ProviderBuilder::new().connect_provider(p);
-                    (*k, provider)
-                })
-        }))
-        .await
-        .into_iter()
-        .filter_map(|v| {
-            v.inspect_err(|err| tracing::warn!(?err, "error establishing connection with the rpc"))
-                .ok()
-        })
-        .collect::<HashMap<_, _>>();
-
-        let chain_methods_map = providers
-            .iter()
-            .map(
-                |(chain, provider)| -> eyre::Result<(NamedChain, RpcModule<GlobalRpcContextT>)> {
-                    let global_ctx = GlobalRpcContext {
-                        sender: self.req_sender.clone(),
-                        provider: Arc::new(provider.clone()),
-                    };
-                    let mut methods = RpcModule::new(global_ctx.clone());
-                    methods.merge(eth::init(global_ctx.clone())?)?;
-                    methods.merge(net::init(global_ctx.clone())?)?;
-                    methods.merge(web3::init(global_ctx.clone())?)?;
-                    methods.merge(wallet::init(global_ctx.clone())?)?;
-                    Ok((*chain, methods))
-                },
-            )
-            .filter_map(|v| {
-                v.inspect_err(|err| tracing::warn!(?err, "error initializing chain rpc module"))
-                    .ok()
-            })
-            .collect::<HashMap<_, _>>();
-        self.providers = providers;
-        self.chain_methods_map = Arc::new(chain_methods_map);
+    /// Reconnects the upstreams of every chain
+    pub async fn reinit(&self) {
+        self.chains.reconnect().await;
+    }
+
+    /// Handle to the served chains, to add, remove or change chains while the server runs
+    pub fn chains(&self) -> &Chains {
+        &self.chains
+    }
+
+    /// Counters of the response cache of each chain
+    pub fn cache_stats(&self) -> HashMap<NamedChain, CacheStats> {
+        self.chains.cache_stats()
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Handle to the state served by the `nexum_` namespace, to report the changes made by the
+    /// user
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// The WebTransport endpoint, once the server runs
+    pub fn webtransport(&self) -> Option<&WebTransportInfo> {
+        self.webtransport.as_ref()
     }
 
     pub async fn run(
@@ -274,140 +1303,108 @@ impl RpcServer {
         ServerHandle,
         Receiver<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
     )> {
-        let listen_addr = SocketAddr::new(self.host.into(), self.port);
-
-        let listener = TcpListener::bind(listen_addr).await?;
+        let mut listeners = Vec::with_capacity(self.hosts.len());
+        for host in &self.hosts {
+            listeners.push(TcpListener::bind(SocketAddr::new(*host, self.port)).await?);
+        }
+        #[cfg(unix)]
+        let ipc_listener = self.ipc_path.as_deref().map(bind_ipc).transpose()?;
+        #[cfg(not(unix))]
+        if self.ipc_path.is_some() {
+            eyre::bail!("IPC is only supported on unix");
+        }
+        let webtransport = self
+            .webtransport_port
+            .map(|port| WebTransportServer::bind(&self.hosts, port))
+            .transpose()?;
+        self.webtransport = webtransport.as_ref().map(|server| server.info().clone());
 
         // Each RPC call/connection get its own `stop_handle` to able to determine whether the server
         // has been stopped or not. To keep the server running the `server_handle` must be kept and it
         // can also be used to stop the server.
         let (stop_handle, server_handle) = stop_channel();
 
-        #[derive(Clone)]
-        struct PerConnection<RpcMiddleware, HttpMiddleware> {
-            methods: Arc<HashMap<NamedChain, RpcModule<GlobalRpcContextT>>>,
-            stop_handle: StopHandle,
-            metrics: Metrics,
-            svc_builder: TowerServiceBuilder<RpcMiddleware, HttpMiddleware>,
-        }
+        // the server can always be reached by the addresses it listens on
+        let mut access = self.access.clone();
+        access
+            .allowed_hosts
+            .extend(self.hosts.iter().map(|host| match host {
+                IpAddr::V4(host) => host.to_string(),
+                IpAddr::V6(host) => format!("[{host}]"),
+            }));
 
         let per_conn_template = PerConnection {
-            methods: self.chain_methods_map.clone(),
+            chains: self.chains.clone(),
+            sender: self.req_sender.clone(),
+            access: Some(access),
             stop_handle: stop_handle.clone(),
             svc_builder: jsonrpsee::server::Server::builder()
-                .max_connections(33)
+                .set_config(
+                    ServerConfig::builder()
+                        .max_connections(33)
+                        .max_request_body_size(MAX_REQUEST_BODY_SIZE)
+                        .max_response_body_size(MAX_RESPONSE_BODY_SIZE)
+                        .set_batch_request_config(self.batch_config)
+                        .build(),
+                )
                 .to_service_builder(),
-            metrics: Metrics::default(),
+            metrics: self.metrics.clone(),
+            status: self.status.clone(),
+            webtransport: self.webtransport.clone(),
         };
 
-        tokio::spawn(async move {
-            loop {
-                // The `tokio::select!` macro is used to wait for either of the
-                // listeners to accept a new connection or for the server to be
-                // stopped.
-                let sock = tokio::select! {
-                    res = listener.accept() => {
-                        match res {
-                            Ok((stream, _remote_addr)) => stream,
-                            Err(e) => {
-                                tracing::error!("failed to accept v4 connection: {:?}", e);
-                                continue;
+        for listener in listeners {
+            let per_conn = per_conn_template.clone();
+            tokio::spawn(async move {
+                loop {
+                    let sock = tokio::select! {
+                        res = listener.accept() => {
+                            match res {
+                                Ok((stream, _remote_addr)) => stream,
+                                Err(e) => {
+                                    tracing::error!("failed to accept tcp connection: {:?}", e);
+                                    continue;
+                                }
                             }
                         }
-                    }
-                    _ = per_conn_template.stop_handle.clone().shutdown() => break,
-                };
+                        _ = per_conn.stop_handle.clone().shutdown() => break,
+                    };
+                    per_conn.clone().serve(sock);
+                }
+            });
+        }
 
-                let per_conn = per_conn_template.clone();
-
-                let svc = tower::service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
-                    // determine the chain of RPC
-                    let chain = chain_id_or_name_to_named_chain(
-                        req.uri()
-                            .path()
-                            .strip_prefix("/")
-                            .unwrap_or_else(|| req.uri().path()),
-                    );
-                    if chain.is_err() {
-                        return async { Err(eyre::eyre!("{:?}", chain.unwrap_err())) }.boxed();
-                    }
-                    let chain = chain.unwrap();
-
-                    let PerConnection {
-                        methods: chain_methods,
-                        stop_handle,
-                        metrics,
-                        svc_builder,
-                    } = per_conn.clone();
-                    let methods = chain_methods.get(&chain);
-                    if methods.is_none() {
-                        return async { Err(eyre::eyre!("chain not configured")) }.boxed();
-                    }
+        if let Some(webtransport) = webtransport {
+            webtransport.spawn(per_conn_template.clone());
+        }
 
-                    let methods = methods.unwrap().clone();
-
-                    let is_websocket = jsonrpsee::server::ws::is_upgrade_request(&req);
-
-                    let rpc_middleware = RpcServiceBuilder::new()
-                        .rpc_logger(1024)
-                        .layer_fn(move |service| CallerContext { service });
-
-                    let mut svc = svc_builder
-                        .set_rpc_middleware(rpc_middleware)
-                        .build(methods, stop_handle.clone());
-
-                    if is_websocket {
-                        // Utilize the session close future to know when the actual WebSocket
-                        // session was closed.
-                        let session_close = svc.on_session_closed();
-
-                        // A little bit weird API but the response to HTTP request must be returned below
-                        // and we spawn a task to register when the session is closed.
-                        tokio::spawn(async move {
-                            session_close.await;
-                            tracing::info!("Closed WebSocket connection");
-                            metrics
-                                .closed_ws_connections
-                                .fetch_add(1, Ordering::Relaxed);
-                        });
-
-                        tracing::info!("Opened WebSocket connection");
-                        metrics
-                            .opened_ws_connections
-                            .fetch_add(1, Ordering::Relaxed);
-                        // https://github.com/rust-lang/rust/issues/102211 the error type can't be inferred
-                        // to be `Box<dyn std::error::Error + Send + Sync>` so we need to convert it to a concrete type
-                        // as workaround.
-                        async move { svc.call(req).await.map_err(|e| eyre::eyre!("{:?}", e)) }
-                            .boxed()
-                    } else {
-                        // HTTP.
-                        tracing::info!("Opened HTTP connection");
-                        metrics.http_calls.fetch_add(1, Ordering::Relaxed);
-                        async move {
-                            let rp = svc.call(req).await;
-
-                            if rp.is_ok() {
-                                metrics.success_http_calls.fetch_add(1, Ordering::Relaxed);
+        #[cfg(unix)]
+        if let Some((listener, path)) = ipc_listener {
+            let per_conn = per_conn_template.clone();
+            tokio::spawn(async move {
+                loop {
+                    let sock = tokio::select! {
+                        res = listener.accept() => {
+                            match res {
+                                Ok((stream, _remote_addr)) => stream,
+                                Err(e) => {
+                                    tracing::error!("failed to accept ipc connection: {:?}", e);
+                                    continue;
+                                }
                             }
-
-                            tracing::info!("Closed HTTP connection");
-                            // https://github.com/rust-lang/rust/issues/102211 the error type can't be inferred
-                            // to be `Box<dyn std::error::Error + Send + Sync>` so we need to convert it to a concrete type
-                            // as workaround.
-                            rp.map_err(|e| eyre::eyre!("{:?}", e))
                         }
-                        .boxed()
-                    }
-                });
-
-                tokio::spawn(serve_with_graceful_shutdown(
-                    sock,
-                    svc,
-                    stop_handle.clone().shutdown(),
-                ));
-            }
-        });
+                        _ = per_conn.stop_handle.clone().shutdown() => break,
+                    };
+                    let per_conn = per_conn.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = per_conn.serve_ipc(sock).await {
+                            tracing::debug!(?err, "ipc connection closed with an error");
+                        }
+                    });
+                }
+                _ = std::fs::remove_file(path);
+            });
+        }
 
         Ok((
             server_handle,
@@ -417,3 +1414,355 @@ impl RpcServer {
         ))
     }
 }
+
+/// State shared by the connections of every listener
+#[derive(Clone)]
+pub(crate) struct PerConnection {
+    chains: Chains,
+    sender: mpsc::Sender<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
+    /// Checks of the requests, `None` on connections that are trusted like IPC ones
+    access: Option<AccessControl>,
+    stop_handle: StopHandle,
+    metrics: Metrics,
+    status: Status,
+    webtransport: Option<WebTransportInfo>,
+    svc_builder: TowerServiceBuilder<Identity, Identity>,
+}
+
+impl PerConnection {
+    pub(crate) fn stop_handle(&self) -> StopHandle {
+        self.stop_handle.clone()
+    }
+
+    /// Returns whether the chain is configured
+    pub(crate) fn serves(&self, chain: &NamedChain) -> bool {
+        self.chains.methods().contains(chain)
+    }
+
+    /// Validates the request, returning the origin of browser requests
+    pub(crate) fn check_access<B>(
+        &self,
+        req: &HttpRequest<B>,
+    ) -> Result<Option<HeaderValue>, Rejection> {
+        self.access
+            .as_ref()
+            .map_or(Ok(None), |access| access.check(req))
+    }
+
+    /// Serves the HTTP and WebSocket requests of the connection
+    fn serve<I>(self, io: I)
+    where
+        I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let stopped = self.stop_handle.clone().shutdown();
+        let svc = tower::service_fn(move |req| self.call(req));
+        tokio::spawn(serve_with_graceful_shutdown(io, svc, stopped));
+    }
+
+    fn call(
+        &self,
+        req: hyper::Request<hyper::body::Incoming>,
+    ) -> BoxFuture<'static, eyre::Result<HttpResponse>> {
+        // keep other websites and DNS rebinding attacks away from the wallet
+        let cors_origin = match self.check_access(&req) {
+            Ok(origin) => origin,
+            Err(rejection) => {
+                let rp = rejection.into_response();
+                return async { Ok(rp) }.boxed();
+            }
+        };
+        if req.method() == hyper::Method::OPTIONS {
+            let rp = AccessControl::preflight(cors_origin.as_ref());
+            return async { Ok(rp) }.boxed();
+        }
+        if req.method() == hyper::Method::GET && req.uri().path() == "/metrics" {
+            let rp = HttpResponse::builder()
+                .header(hyper::header::CONTENT_TYPE, METRICS_CONTENT_TYPE)
+                .body(HttpBody::from(
+                    self.metrics.render(&self.chains.cache_stats()),
+                ))
+                .expect("metrics response is valid");
+            return async { Ok(rp) }.boxed();
+        }
+        if req.method() == hyper::Method::GET
+            && req.uri().path() == "/webtransport"
+            && let Some(webtransport) = &self.webtransport
+        {
+            let mut rp = HttpResponse::builder()
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(HttpBody::from(webtransport.to_json()))
+                .expect("webtransport response is valid");
+            AccessControl::allow_origin(&mut rp, cors_origin.as_ref());
+            return async { Ok(rp) }.boxed();
+        }
+
+        // determine the chain of RPC
+        let chain = chain_id_or_name_to_named_chain(
+            req.uri()
+                .path()
+                .strip_prefix("/")
+                .unwrap_or_else(|| req.uri().path()),
+        );
+        if chain.is_err() {
+            return async { Err(eyre::eyre!("{:?}", chain.unwrap_err())) }.boxed();
+        }
+        let chain = chain.unwrap();
+
+        let PerConnection {
+            chains,
+            sender,
+            access: _,
+            stop_handle,
+            metrics,
+            status,
+            webtransport: _,
+            svc_builder,
+        } = self.clone();
+        let methods = chains.methods().get(&chain);
+        if methods.is_none() {
+            return async { Err(eyre::eyre!("chain not configured")) }.boxed();
+        }
+
+        let methods = methods.unwrap();
+
+        // browsers always send the origin of the page, a value that isn't a valid
+        // url (e.g. `null` from sandboxed frames) can't be granted permissions
+        let origin = match req.headers().get(hyper::header::ORIGIN) {
+            Some(origin) => match origin.to_str().map(Url::parse) {
+                Ok(Ok(origin)) => Some(origin),
+                _ => return async { Err(eyre::eyre!("invalid origin")) }.boxed(),
+            },
+            None => None,
+        };
+        let session = Session::new(chains, chain, origin);
+        let mut req = req;
+        req.extensions_mut().insert(session.clone());
+
+        let is_websocket = jsonrpsee::server::ws::is_upgrade_request(&req);
+
+        let rpc_metrics = metrics.clone();
+        let rpc_middleware = RpcServiceBuilder::new()
+            .rpc_logger(1024)
+            .layer_fn(move |service| CallerContext {
+                service,
+                sender: sender.clone(),
+                metrics: rpc_metrics.clone(),
+                status: status.clone(),
+            });
+
+        // only the subscriptions are served by the methods the connection was opened with, they
+        // are made on the chain the session is active on when subscribing
+        let mut svc = svc_builder
+            .set_rpc_middleware(rpc_middleware)
+            .build(methods, stop_handle.clone());
+
+        if is_websocket {
+            // Utilize the session close future to know when the actual WebSocket
+            // session was closed.
+            let session_close = svc.on_session_closed();
+
+            // A little bit weird API but the response to HTTP request must be returned below
+            // and we spawn a task to register when the session is closed.
+            let ws_metrics = metrics.clone();
+            tokio::spawn(async move {
+                session_close.await;
+                // tears down the subscriptions of the session
+                session.close();
+                tracing::info!("Closed WebSocket connection");
+                ws_metrics.ws_closed();
+            });
+
+            tracing::info!("Opened WebSocket connection");
+            metrics.ws_opened();
+            // https://github.com/rust-lang/rust/issues/102211 the error type can't be inferred
+            // to be `Box<dyn std::error::Error + Send + Sync>` so we need to convert it to a concrete type
+            // as workaround.
+            async move { svc.call(req).await.map_err(|e| eyre::eyre!("{:?}", e)) }.boxed()
+        } else {
+            // HTTP.
+            tracing::info!("Opened HTTP connection");
+            metrics.http_call();
+            async move {
+                let mut rp = svc.call(req).await;
+                if let Ok(rp) = &mut rp {
+                    AccessControl::allow_origin(rp, cors_origin.as_ref());
+                }
+
+                if rp.is_ok() {
+                    metrics.http_call_succeeded();
+                }
+
+                tracing::info!("Closed HTTP connection");
+                // https://github.com/rust-lang/rust/issues/102211 the error type can't be inferred
+                // to be `Box<dyn std::error::Error + Send + Sync>` so we need to convert it to a concrete type
+                // as workaround.
+                rp.map_err(|e| eyre::eyre!("{:?}", e))
+            }
+            .boxed()
+        }
+    }
+
+    /// Serves a connection speaking raw JSON-RPC, like the IPC clients of Foundry or geth. The
+    /// session starts on mainnet, or on the first configured chain. The socket file's permissions
+    /// decide who may connect.
+    #[cfg(unix)]
+    async fn serve_ipc(self, stream: UnixStream) -> eyre::Result<()> {
+        let chains = self.chains.methods().chains();
+        let chain = if chains.contains(&NamedChain::Mainnet) {
+            NamedChain::Mainnet
+        } else {
+            chains
+                .into_iter()
+                .min_by_key(|chain| *chain as u64)
+                .ok_or_eyre("no chain configured")?
+        };
+        self.serve_raw(stream, chain, None).await
+    }
+
+    /// Serves a stream of raw JSON-RPC messages. The messages are relayed to a WebSocket session
+    /// served in memory, so raw clients get the sessions and subscriptions of WebSocket clients.
+    pub(crate) async fn serve_raw<I>(
+        self,
+        io: I,
+        chain: NamedChain,
+        origin: Option<HeaderValue>,
+    ) -> eyre::Result<()>
+    where
+        I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut headers = hyper::HeaderMap::new();
+        if let Some(origin) = origin {
+            headers.insert(hyper::header::ORIGIN, origin);
+        }
+        let (client, server) = tokio::io::duplex(RAW_BUFFER_SIZE);
+        // the connection has been checked by its listener already
+        Self {
+            access: None,
+            ..self
+        }
+        .serve(server);
+        let (mut sender, mut receiver) = WsTransportClientBuilder::default()
+            .max_request_size(MAX_REQUEST_BODY_SIZE)
+            .max_response_size(MAX_RESPONSE_BODY_SIZE)
+            .set_headers(headers)
+            .build_with_stream(format!("ws://localhost/{}", chain as u64).parse()?, client)
+            .await?;
+
+        let (mut reader, mut writer) = tokio::io::split(io);
+        let requests = async {
+            let mut buf = Vec::new();
+            loop {
+                if reader.read_buf(&mut buf).await? == 0 {
+                    return Ok(());
+                }
+                for msg in split_json_messages(&mut buf)? {
+                    sender.send(msg).await?;
+                }
+            }
+        };
+        let responses = async {
+            loop {
+                let msg = match receiver.receive().await? {
+                    ReceivedMessage::Text(msg) => msg.into_bytes(),
+                    ReceivedMessage::Bytes(msg) => msg,
+                    ReceivedMessage::Pong => continue,
+                };
+                writer.write_all(&msg).await?;
+                writer.write_all(b"\n").await?;
+            }
+        };
+        tokio::select! {
+            res = requests => res,
+            res = responses => res,
+        }
+    }
+}
+
+/// Binds the IPC socket, only accessible to the current user, replacing the socket left behind by
+/// a server that didn't shut down cleanly
+#[cfg(unix)]
+fn bind_ipc(path: &Path) -> eyre::Result<(UnixListener, PathBuf)> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            eyre::bail!("{} exists and isn't a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            eyre::bail!("{} is in use by another server", path.display());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok((listener, path.to_path_buf()))
+}
+
+/// Takes the complete JSON values out of the buffer, leaving an incomplete trailing one for the
+/// next read
+fn split_json_messages(buf: &mut Vec<u8>) -> eyre::Result<Vec<String>> {
+    let mut messages = Vec::new();
+    let mut values =
+        serde_json::Deserializer::from_slice(buf).into_iter::<&serde_json::value::RawValue>();
+    for value in values.by_ref() {
+        match value {
+            Ok(value) => messages.push(value.get().to_string()),
+            Err(err) if err.is_eof() => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let consumed = values.byte_offset();
+    buf.drain(..consumed);
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::b256;
+    use serde_json::json;
+
+    use super::*;
+
+    fn legacy_typed_data(fields: serde_json::Value) -> LegacyTypedData {
+        serde_json::from_value(fields).unwrap()
+    }
+
+    #[test]
+    fn hashes_legacy_typed_data() {
+        // typedSignatureHash of @metamask/eth-sig-util
+        let data = legacy_typed_data(json!([
+            { "type": "string", "name": "message", "value": "Hi, Alice!" },
+        ]));
+        assert_eq!(
+            data.signature_hash().unwrap(),
+            b256!("0x14b9f24872e28cc49e72dc104d7380d8e0ba84a3fe2e712704bcac66a5702bd5")
+        );
+
+        // the schemas and the values of the fields are packed one after the other
+        let data = legacy_typed_data(json!([
+            { "type": "string", "name": "message", "value": "Hi, Alice!" },
+            { "type": "uint32", "name": "A number", "value": "1337" },
+        ]));
+        let values = [b"Hi, Alice!".as_slice(), &1337u32.to_be_bytes()].concat();
+        let expected = keccak256(
+            [
+                keccak256("string messageuint32 A number"),
+                keccak256(values),
+            ]
+            .concat(),
+        );
+        assert_eq!(data.signature_hash().unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_legacy_typed_data_that_does_not_encode() {
+        let data = legacy_typed_data(json!([
+            { "type": "uint8", "name": "count", "value": "256" },
+        ]));
+        assert!(data.signature_hash().is_err());
+        let data = legacy_typed_data(json!([
+            { "type": "not a type", "name": "field", "value": "1" },
+        ]));
+        assert!(data.signature_hash().is_err());
+    }
+}
@@ -0,0 +1,536 @@
+use std::{borrow::Cow, fmt};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    hex,
+    primitives::{Address, B256, Bytes, LogData, U256, address},
+    providers::Provider,
+    rpc::types::{
+        TransactionRequest,
+        simulate::{SimBlock, SimCallResult, SimulatePayload},
+    },
+    sol,
+    sol_types::{GenericContractError, SolEvent, SolInterface},
+    transports::{RpcError, TransportError, TransportResult},
+};
+use serde::Deserialize;
+
+/// Address `eth_simulateV1` emits the ERC-20 `Transfer` logs of native currency transfers from
+const NATIVE_TRANSFERS: Address = address!("0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE");
+
+sol! {
+    interface IERC20 {
+        event Transfer(address indexed from, address indexed to, uint256 value);
+        event Approval(address indexed owner, address indexed spender, uint256 value);
+    }
+
+    interface IERC721 {
+        event Transfer(address indexed from, address indexed to, uint256 indexed tokenId);
+        event Approval(address indexed owner, address indexed approved, uint256 indexed tokenId);
+        event ApprovalForAll(address indexed owner, address indexed operator, bool approved);
+    }
+
+    interface IERC1155 {
+        event TransferSingle(
+            address indexed operator,
+            address indexed from,
+            address indexed to,
+            uint256 id,
+            uint256 value
+        );
+        event TransferBatch(
+            address indexed operator,
+            address indexed from,
+            address indexed to,
+            uint256[] ids,
+            uint256[] values
+        );
+    }
+}
+
+/// Outcome of running a transaction against the latest block before it's signed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Simulation {
+    /// The transaction succeeds, estimated to use the gas. The changes to the assets of the
+    /// sender are `None` if the node can't tell them.
+    Success {
+        gas: u64,
+        changes: Option<Vec<AssetChange>>,
+    },
+    /// The transaction reverts, with the reason decoded from the revert data if any
+    Revert(Option<String>),
+    /// The node refuses the transaction for another reason, e.g. insufficient funds
+    Failure(String),
+    /// The transaction couldn't be simulated, e.g. the upstreams can't be reached
+    Unavailable(String),
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success { gas, .. } => write!(f, "ok, estimated gas {gas}"),
+            Self::Revert(Some(reason)) => write!(f, "will revert: {reason}"),
+            Self::Revert(None) => write!(f, "will revert without a reason"),
+            Self::Failure(message) => write!(f, "will fail: {message}"),
+            Self::Unavailable(message) => write!(f, "couldn't simulate: {message}"),
+        }
+    }
+}
+
+/// Change a transaction makes to the assets of its sender
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetChange {
+    Send {
+        asset: Asset,
+        to: Address,
+    },
+    Receive {
+        asset: Asset,
+        from: Address,
+    },
+    /// The sender allows the spender to transfer the asset
+    Approve {
+        asset: Asset,
+        spender: Address,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Asset {
+    /// Amount of the native currency of the chain
+    Native(U256),
+    Erc20 {
+        token: Address,
+        amount: U256,
+    },
+    Erc721 {
+        token: Address,
+        id: U256,
+    },
+    Erc1155 {
+        token: Address,
+        id: U256,
+        amount: U256,
+    },
+    /// Every token of the ERC-721 or ERC-1155 contract, approved with `setApprovalForAll`
+    All(Address),
+}
+
+/// Simulates the transaction with `eth_call`, then estimates its gas with `eth_estimateGas` and
+/// previews the changes to the assets of its sender
+pub async fn simulate<P: Provider>(provider: &P, tx: TransactionRequest) -> Simulation {
+    if let Err(err) = provider.call(tx.clone()).await {
+        return failed(err);
+    }
+    match provider.estimate_gas(tx.clone()).await {
+        Ok(gas) => Simulation::Success {
+            gas,
+            changes: asset_changes(provider, tx).await,
+        },
+        Err(err) => failed(err),
+    }
+}
+
+/// Changes the transaction makes to the assets of its sender, read from the logs of
+/// `eth_simulateV1` or, on nodes that don't support it, of a `debug_traceCall` call trace.
+/// `None` if the node supports neither.
+pub async fn asset_changes<P: Provider>(
+    provider: &P,
+    tx: TransactionRequest,
+) -> Option<Vec<AssetChange>> {
+    let account = tx.from?;
+    let logs = match simulated_logs(provider, tx.clone()).await {
+        Ok(logs) => logs,
+        Err(err) => {
+            tracing::debug!(
+                ?err,
+                "eth_simulateV1 failed, falling back to debug_traceCall"
+            );
+            traced_logs(provider, tx)
+                .await
+                .inspect_err(|err| tracing::debug!(?err, "debug_traceCall failed"))
+                .ok()?
+        }
+    };
+    Some(
+        logs.into_iter()
+            .flat_map(|(token, log)| log_changes(account, token, &log))
+            .collect(),
+    )
+}
+
+/// Block returned by `eth_simulateV1`, only the results of the calls are used
+#[derive(Debug, Deserialize)]
+struct SimulatedBlock {
+    calls: Vec<SimCallResult>,
+}
+
+/// Logs emitted by the transaction along with the address of their contract, native currency
+/// transfers are traced as ERC-20 transfers of [`NATIVE_TRANSFERS`]
+async fn simulated_logs<P: Provider>(
+    provider: &P,
+    tx: TransactionRequest,
+) -> TransportResult<Vec<(Address, LogData)>> {
+    let payload = SimulatePayload {
+        block_state_calls: vec![SimBlock {
+            calls: vec![tx],
+            ..Default::default()
+        }],
+        trace_transfers: true,
+        ..Default::default()
+    };
+    let blocks: Vec<SimulatedBlock> = provider
+        .raw_request(
+            Cow::Borrowed("eth_simulateV1"),
+            (payload, BlockNumberOrTag::Latest),
+        )
+        .await?;
+    Ok(blocks
+        .into_iter()
+        .flat_map(|block| block.calls)
+        .filter(|call| call.status)
+        .flat_map(|call| call.logs)
+        .map(|log| (log.address(), log.inner.data))
+        .collect())
+}
+
+/// Frame of the `callTracer` of `debug_traceCall`
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    #[serde(rename = "type")]
+    kind: String,
+    from: Address,
+    to: Option<Address>,
+    value: Option<U256>,
+    error: Option<String>,
+    #[serde(default)]
+    logs: Vec<CallLog>,
+    #[serde(default)]
+    calls: Vec<CallFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallLog {
+    address: Address,
+    topics: Vec<B256>,
+    data: Bytes,
+}
+
+/// Logs of the call trace, in the same form as [`simulated_logs`]
+async fn traced_logs<P: Provider>(
+    provider: &P,
+    tx: TransactionRequest,
+) -> TransportResult<Vec<(Address, LogData)>> {
+    let tracer = serde_json::json!({
+        "tracer": "callTracer",
+        "tracerConfig": { "withLog": true },
+    });
+    let frame: CallFrame = provider
+        .raw_request(
+            Cow::Borrowed("debug_traceCall"),
+            (tx, BlockNumberOrTag::Latest, tracer),
+        )
+        .await?;
+    let mut logs = Vec::new();
+    collect_logs(frame, &mut logs);
+    Ok(logs)
+}
+
+fn collect_logs(frame: CallFrame, logs: &mut Vec<(Address, LogData)>) {
+    // the changes of reverted calls are undone
+    if frame.error.is_some() {
+        return;
+    }
+    // delegate and static calls don't move the native currency
+    if matches!(
+        frame.kind.as_str(),
+        "CALL" | "CREATE" | "CREATE2" | "SELFDESTRUCT"
+    ) && let (Some(to), Some(value)) = (frame.to, frame.value)
+        && !value.is_zero()
+    {
+        let transfer = IERC20::Transfer {
+            from: frame.from,
+            to,
+            value,
+        };
+        logs.push((NATIVE_TRANSFERS, transfer.encode_log_data()));
+    }
+    for log in frame.logs {
+        logs.push((log.address, LogData::new_unchecked(log.topics, log.data)));
+    }
+    for call in frame.calls {
+        collect_logs(call, logs);
+    }
+}
+
+/// Changes to the assets of the account made by the log of the token contract
+fn log_changes(account: Address, token: Address, log: &LogData) -> Vec<AssetChange> {
+    // ERC-20 and ERC-721 events share their signatures, they differ by their indexed fields
+    if let Ok(transfer) = IERC20::Transfer::decode_log_data(log) {
+        let asset = if token == NATIVE_TRANSFERS {
+            Asset::Native(transfer.value)
+        } else {
+            Asset::Erc20 {
+                token,
+                amount: transfer.value,
+            }
+        };
+        return transfer_changes(account, transfer.from, transfer.to, asset);
+    }
+    if let Ok(transfer) = IERC721::Transfer::decode_log_data(log) {
+        let asset = Asset::Erc721 {
+            token,
+            id: transfer.tokenId,
+        };
+        return transfer_changes(account, transfer.from, transfer.to, asset);
+    }
+    if let Ok(transfer) = IERC1155::TransferSingle::decode_log_data(log) {
+        let asset = Asset::Erc1155 {
+            token,
+            id: transfer.id,
+            amount: transfer.value,
+        };
+        return transfer_changes(account, transfer.from, transfer.to, asset);
+    }
+    if let Ok(transfer) = IERC1155::TransferBatch::decode_log_data(log) {
+        return transfer
+            .ids
+            .iter()
+            .zip(&transfer.values)
+            .flat_map(|(id, amount)| {
+                let asset = Asset::Erc1155 {
+                    token,
+                    id: *id,
+                    amount: *amount,
+                };
+                transfer_changes(account, transfer.from, transfer.to, asset)
+            })
+            .collect();
+    }
+    if let Ok(approval) = IERC20::Approval::decode_log_data(log)
+        && approval.owner == account
+    {
+        let asset = Asset::Erc20 {
+            token,
+            amount: approval.value,
+        };
+        return vec![AssetChange::Approve {
+            asset,
+            spender: approval.spender,
+        }];
+    }
+    if let Ok(approval) = IERC721::Approval::decode_log_data(log)
+        && approval.owner == account
+        // approving the zero address clears the approval
+        && !approval.approved.is_zero()
+    {
+        let asset = Asset::Erc721 {
+            token,
+            id: approval.tokenId,
+        };
+        return vec![AssetChange::Approve {
+            asset,
+            spender: approval.approved,
+        }];
+    }
+    if let Ok(approval) = IERC721::ApprovalForAll::decode_log_data(log)
+        && approval.owner == account
+        && approval.approved
+    {
+        return vec![AssetChange::Approve {
+            asset: Asset::All(token),
+            spender: approval.operator,
+        }];
+    }
+    Vec::new()
+}
+
+fn transfer_changes(
+    account: Address,
+    from: Address,
+    to: Address,
+    asset: Asset,
+) -> Vec<AssetChange> {
+    if from == to {
+        Vec::new()
+    } else if from == account {
+        vec![AssetChange::Send { asset, to }]
+    } else if to == account {
+        vec![AssetChange::Receive { asset, from }]
+    } else {
+        Vec::new()
+    }
+}
+
+fn failed(err: TransportError) -> Simulation {
+    match err {
+        RpcError::ErrorResp(payload) => match payload.as_revert_data() {
+            Some(data) => Simulation::Revert(revert_reason(&data)),
+            // some nodes only give the reason in the message
+            None => match payload.message.strip_prefix("execution reverted") {
+                Some(reason) => Simulation::Revert(
+                    Some(reason.trim_start_matches(':').trim())
+                        .filter(|reason| !reason.is_empty())
+                        .map(String::from),
+                ),
+                None => Simulation::Failure(payload.message.into_owned()),
+            },
+        },
+        err => Simulation::Unavailable(err.to_string()),
+    }
+}
+
+/// Decodes the `Error(string)` and `Panic(uint256)` revert data of Solidity, other errors are
+/// shown by their selector
+pub fn revert_reason(data: &[u8]) -> Option<String> {
+    match GenericContractError::abi_decode(data) {
+        Ok(GenericContractError::Revert(revert)) => Some(revert.reason),
+        Ok(GenericContractError::Panic(panic)) => Some(panic.to_string()),
+        Ok(GenericContractError::CustomError(never)) => match never {},
+        Err(_) => data
+            .get(..4)
+            .map(|selector| format!("custom error 0x{}", hex::encode(selector))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::{U64, hex},
+        providers::{ProviderBuilder, mock::Asserter},
+        rpc::json_rpc::ErrorPayload,
+        sol_types::{Panic, PanicKind, Revert, SolError},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const SENDER: Address = address!("0x1111111111111111111111111111111111111111");
+    const RECIPIENT: Address = address!("0x2222222222222222222222222222222222222222");
+    const TOKEN: Address = address!("0x3333333333333333333333333333333333333333");
+
+    fn transfer() -> TransactionRequest {
+        TransactionRequest::default()
+            .from(SENDER)
+            .to(RECIPIENT)
+            .value(U256::from(1))
+    }
+
+    fn reverted(data: &[u8]) -> ErrorPayload {
+        ErrorPayload {
+            code: 3,
+            message: "execution reverted".into(),
+            data: serde_json::value::to_raw_value(&hex::encode_prefixed(data)).ok(),
+        }
+    }
+
+    #[test]
+    fn decodes_revert_reasons() {
+        let revert = Revert::from("not the owner").abi_encode();
+        assert_eq!(revert_reason(&revert).as_deref(), Some("not the owner"));
+        let panic = Panic::from(PanicKind::UnderOverflow).abi_encode();
+        assert_eq!(
+            revert_reason(&panic),
+            Some(Panic::from(PanicKind::UnderOverflow).to_string())
+        );
+        assert_eq!(
+            revert_reason(&hex!("0xdeadbeef0000")).as_deref(),
+            Some("custom error 0xdeadbeef")
+        );
+        assert_eq!(revert_reason(&hex!("0xdead")), None);
+    }
+
+    #[tokio::test]
+    async fn reverting_calls_are_decoded() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+
+        asserter.push_failure(reverted(&Revert::from("not the owner").abi_encode()));
+        assert_eq!(
+            simulate(&provider, transfer()).await,
+            Simulation::Revert(Some("not the owner".to_string()))
+        );
+
+        asserter.push_failure(reverted(&hex!("0xdeadbeef")));
+        assert_eq!(
+            simulate(&provider, transfer()).await,
+            Simulation::Revert(Some("custom error 0xdeadbeef".to_string()))
+        );
+
+        // some nodes only give the reason in the message
+        asserter.push_failure_msg("execution reverted: not the owner");
+        assert_eq!(
+            simulate(&provider, transfer()).await,
+            Simulation::Revert(Some("not the owner".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_gas_estimates_fail_the_simulation() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+        asserter.push_success(&Bytes::new());
+        asserter.push_failure_msg("insufficient funds for gas * price + value");
+        assert_eq!(
+            simulate(&provider, transfer()).await,
+            Simulation::Failure("insufficient funds for gas * price + value".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_call_traces() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new().connect_mocked_client(asserter.clone());
+        let transfer_log = IERC20::Transfer {
+            from: SENDER,
+            to: RECIPIENT,
+            value: U256::from(5),
+        }
+        .encode_log_data();
+        let log = json!({
+            "address": TOKEN,
+            "topics": transfer_log.topics(),
+            "data": transfer_log.data,
+        });
+        asserter.push_success(&Bytes::new());
+        asserter.push_success(&U64::from(50_000));
+        asserter.push_failure_msg("the method eth_simulateV1 does not exist");
+        asserter.push_success(&json!({
+            "type": "CALL",
+            "from": SENDER,
+            "to": RECIPIENT,
+            "value": "0x1",
+            "calls": [
+                { "type": "CALL", "from": RECIPIENT, "to": TOKEN, "logs": [log] },
+                // the changes of reverted calls are undone
+                {
+                    "type": "CALL",
+                    "from": RECIPIENT,
+                    "to": TOKEN,
+                    "error": "execution reverted",
+                    "logs": [log],
+                },
+            ],
+        }));
+
+        assert_eq!(
+            simulate(&provider, transfer()).await,
+            Simulation::Success {
+                gas: 50_000,
+                changes: Some(vec![
+                    AssetChange::Send {
+                        asset: Asset::Native(U256::from(1)),
+                        to: RECIPIENT,
+                    },
+                    AssetChange::Send {
+                        asset: Asset::Erc20 {
+                            token: TOKEN,
+                            amount: U256::from(5),
+                        },
+                        to: RECIPIENT,
+                    },
+                ]),
+            }
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy_chains::NamedChain;
+use serde::Serialize;
+use tokio::sync::watch;
+use url::Url;
+
+/// State of the wallet served by the `nexum_` namespace. The handle is shared by the server, the
+/// sessions and the UI, which reports its changes to the subscribers of `nexum_subscribeStatus`.
+#[derive(Clone, Debug)]
+pub struct Status(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    /// Unix time the server started at, in seconds
+    started_at: u64,
+    pending: Mutex<BTreeMap<u64, PendingRequest>>,
+    next_id: AtomicU64,
+    changes: watch::Sender<()>,
+}
+
+impl Inner {
+    fn lock_pending(&self) -> MutexGuard<'_, BTreeMap<u64, PendingRequest>> {
+        self.pending
+            .lock()
+            .expect("failed to lock pending requests")
+    }
+}
+
+/// A call waiting on the user, e.g. for the approval of a transaction
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingRequest {
+    pub id: u64,
+    pub method: String,
+    pub chain_id: Option<u64>,
+    pub origin: Option<Url>,
+    /// Unix time the user was prompted at, in seconds
+    pub since: u64,
+}
+
+/// A pending request, done when dropped
+#[derive(Debug)]
+pub struct Pending {
+    status: Status,
+    id: u64,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.status.0.lock_pending().remove(&self.id);
+        self.status.changed();
+    }
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self(Arc::new(Inner {
+            started_at: unix_time(),
+            pending: Default::default(),
+            next_id: Default::default(),
+            changes: watch::Sender::new(()),
+        }))
+    }
+}
+
+impl Status {
+    /// Unix time the server started at, in seconds
+    pub fn started_at(&self) -> u64 {
+        self.0.started_at
+    }
+
+    /// Records the call as pending until the returned guard is dropped
+    pub fn pending(&self, method: &str, chain: Option<NamedChain>, origin: Option<Url>) -> Pending {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        self.0.lock_pending().insert(
+            id,
+            PendingRequest {
+                id,
+                method: method.to_string(),
+                chain_id: chain.map(|chain| chain as u64),
+                origin,
+                since: unix_time(),
+            },
+        );
+        self.changed();
+        Pending {
+            status: self.clone(),
+            id,
+        }
+    }
+
+    /// Pending requests made by the origin, oldest first. Clients without an origin see the
+    /// requests of every client.
+    pub fn pending_requests(&self, origin: Option<&Url>) -> Vec<PendingRequest> {
+        self.0
+            .lock_pending()
+            .values()
+            .filter(|request| origin.is_none_or(|origin| request.origin.as_ref() == Some(origin)))
+            .cloned()
+            .collect()
+    }
+
+    /// Notifies the subscribers that the state of the wallet changed, e.g. the active account
+    pub fn changed(&self) {
+        self.0.changes.send_replace(());
+    }
+
+    /// Receiver notified of every change
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.0.changes.subscribe()
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
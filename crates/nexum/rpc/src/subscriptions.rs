@@ -0,0 +1,118 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{
+    providers::{Provider, RootProvider},
+    pubsub::RawSubscription,
+    transports::TransportResult,
+};
+use serde_json::value::RawValue;
+use tokio::sync::{Mutex, broadcast, oneshot};
+
+use crate::upstream::Upstreams;
+
+/// Capacity of the channel that fans out the notifications, sessions lagging further behind
+/// skip the missed notifications
+const NOTIFICATIONS_CAPACITY: usize = 256;
+
+/// Upstream subscriptions of a chain. Sessions subscribing with the same parameters share a
+/// single upstream subscription, which is torn down once the last session is gone.
+#[derive(Clone, Debug, Default)]
+pub struct Subscriptions(Arc<Mutex<HashMap<String, UpstreamSubscription>>>);
+
+#[derive(Debug)]
+struct UpstreamSubscription {
+    notifications: broadcast::Sender<Box<RawValue>>,
+    /// Stops forwarding the notifications when dropped
+    _stop: oneshot::Sender<()>,
+}
+
+impl Subscriptions {
+    /// Subscribes to the notifications for the `eth_subscribe` parameters, opening the upstream
+    /// subscription on one of the upstreams if no other session has done so yet. Returns the key
+    /// to [`Subscriptions::release`] the subscription with.
+    pub async fn subscribe(
+        &self,
+        upstreams: &Upstreams,
+        params: serde_json::Value,
+    ) -> TransportResult<(String, broadcast::Receiver<Box<RawValue>>)> {
+        let key = params.to_string();
+        let mut subscriptions = self.0.lock().await;
+        if let Some(subscription) = subscriptions.get(&key) {
+            return Ok((key, subscription.notifications.subscribe()));
+        }
+
+        let provider = upstreams.pubsub()?;
+        let upstream = provider
+            .subscribe::<_, serde_json::Value>(params)
+            .await?
+            .into_raw();
+        let (notifications, receiver) = broadcast::channel(NOTIFICATIONS_CAPACITY);
+        let (stop, stop_receiver) = oneshot::channel();
+        tokio::spawn(self.clone().forward(
+            key.clone(),
+            provider.clone(),
+            upstream,
+            notifications.clone(),
+            stop_receiver,
+        ));
+        subscriptions.insert(
+            key.clone(),
+            UpstreamSubscription {
+                notifications,
+                _stop: stop,
+            },
+        );
+        Ok((key, receiver))
+    }
+
+    /// Tears down the upstream subscription if no session is subscribed anymore. The receiver
+    /// of the session must have been dropped before.
+    pub async fn release(&self, key: &str) {
+        let mut subscriptions = self.0.lock().await;
+        if subscriptions
+            .get(key)
+            .is_some_and(|subscription| subscription.notifications.receiver_count() == 0)
+        {
+            tracing::debug!(key, "tearing down upstream subscription");
+            subscriptions.remove(key);
+        }
+    }
+
+    async fn forward(
+        self,
+        key: String,
+        provider: RootProvider,
+        mut upstream: RawSubscription,
+        notifications: broadcast::Sender<Box<RawValue>>,
+        mut stop: oneshot::Receiver<()>,
+    ) {
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                notification = upstream.recv() => match notification {
+                    // there might be no session subscribed for a moment, that's fine
+                    Ok(notification) => _ = notifications.send(notification),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!(key, n, "upstream subscription lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        tracing::warn!(key, "upstream subscription closed");
+                        // the sessions are notified by dropping all the senders
+                        let mut subscriptions = self.0.lock().await;
+                        if subscriptions.get(&key).is_some_and(|subscription| {
+                            subscription.notifications.same_channel(&notifications)
+                        }) {
+                            subscriptions.remove(&key);
+                        }
+                        return;
+                    }
+                },
+            }
+        }
+
+        provider
+            .unsubscribe(*upstream.local_id())
+            .inspect_err(|e| tracing::warn!(?e, key, "failed to unsubscribe upstream"))
+            .ok();
+    }
+}
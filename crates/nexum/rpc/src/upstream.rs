@@ -0,0 +1,345 @@
+use std::{
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    providers::{Provider, RootProvider},
+    rpc::{
+        client::RpcClient,
+        json_rpc::{RequestPacket, ResponsePacket},
+    },
+    transports::{
+        RpcError, TransportError, TransportErrorKind, TransportFut, TransportResult,
+        utils::guess_local_url,
+    },
+};
+use alloy_chains::NamedChain;
+use tower::Service;
+use url::Url;
+
+/// Interval between two health probes of the upstreams
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+/// Time an upstream has to answer a request before the next one is tried
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time an upstream has to answer a health probe or to connect
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How far, in time, the head of an upstream may trail the best known head before the upstream
+/// is considered stale
+const MAX_HEAD_LAG: Duration = Duration::from_secs(30);
+/// Minimum number of blocks an upstream may trail the best known head, covers the upstreams
+/// being probed a few moments apart on fast chains
+const MIN_BLOCK_LAG: u64 = 3;
+/// An upstream whose head hasn't advanced for this many block times is considered stalled
+const STALL_BLOCKS: u32 = 10;
+/// Lower bound of the stall timeout
+const MIN_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+/// Methods that mustn't be sent twice, such as broadcasts that would be repeated by another
+/// upstream. They don't fail over once the request may have reached an upstream.
+const NON_IDEMPOTENT_METHODS: [&str; 3] = [
+    "eth_sendRawTransaction",
+    "eth_sendTransaction",
+    "eth_sendRawTransactionConditional",
+];
+
+/// Ordered list of the upstream rpcs of a chain, used as the transport of the chain's provider.
+///
+/// Requests go to the first healthy upstream, and fail over to the next one when it errors or
+/// times out, unless they can't be repeated like transaction broadcasts. A background task probes the head of every upstream, reconnects the ones that are
+/// down and takes the ones that are lagging behind or stalled out of rotation until they catch
+/// up.
+#[derive(Clone, Debug)]
+pub struct Upstreams(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    chain: NamedChain,
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: Url,
+    provider: RwLock<Option<RootProvider>>,
+    health: RwLock<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    healthy: bool,
+    /// The upstream serves another chain, it's never used nor reconnected
+    wrong_chain: bool,
+    head: u64,
+    /// When the head was last seen advancing
+    advanced_at: Option<Instant>,
+}
+
+impl Upstreams {
+    /// Connects to the upstreams and starts probing them. Upstreams that can't be reached are
+    /// retried in the background, so the chain is served as soon as any of them comes up.
+    pub async fn connect(chain: NamedChain, urls: &[Url]) -> Self {
+        let this = Self(Arc::new(Inner {
+            chain,
+            endpoints: urls
+                .iter()
+                .map(|url| Endpoint {
+                    url: url.clone(),
+                    provider: Default::default(),
+                    health: Default::default(),
+                })
+                .collect(),
+        }));
+        this.0.probe().await;
+        tokio::spawn(Inner::run_probes(Arc::downgrade(&this.0)));
+        this
+    }
+
+    pub fn chain(&self) -> NamedChain {
+        self.0.chain
+    }
+
+    /// Returns whether any upstream is currently healthy
+    pub fn is_healthy(&self) -> bool {
+        self.0.endpoints.iter().any(Endpoint::is_healthy)
+    }
+
+    /// Provider backed by these upstreams
+    pub fn provider(&self) -> RootProvider {
+        let is_local = self
+            .0
+            .endpoints
+            .iter()
+            .all(|endpoint| guess_local_url(endpoint.url.as_str()));
+        RootProvider::new(RpcClient::new(self.clone(), is_local))
+    }
+
+    /// Provider of the first healthy upstream that supports subscriptions. Subscriptions are
+    /// bound to the connection they were opened on, so they don't fail over.
+    pub fn pubsub(&self) -> TransportResult<RootProvider> {
+        self.0
+            .candidates()
+            .filter_map(Endpoint::provider)
+            .find(|provider| provider.client().pubsub_frontend().is_some())
+            .ok_or_else(TransportErrorKind::pubsub_unavailable)
+    }
+
+    async fn send(self, req: RequestPacket) -> TransportResult<ResponsePacket> {
+        let idempotent = !req
+            .method_names()
+            .any(|method| NON_IDEMPOTENT_METHODS.contains(&method));
+        let mut last_err = None;
+        for endpoint in self.0.candidates() {
+            let Some(provider) = endpoint.provider() else {
+                continue;
+            };
+            let mut transport = provider.client().transport().clone();
+            let err = match tokio::time::timeout(REQUEST_TIMEOUT, transport.call(req.clone())).await
+            {
+                Ok(Ok(res)) => return Ok(res),
+                // the request reached the node, another upstream would answer the same
+                Ok(Err(err @ RpcError::ErrorResp(_))) => return Err(err),
+                Ok(Err(err)) => err,
+                Err(_) => TransportErrorKind::custom_str("upstream request timed out"),
+            };
+            endpoint.mark_unhealthy(&err);
+            // the upstream may have broadcast the transaction before failing or timing out
+            if !idempotent {
+                tracing::warn!(
+                    ?err,
+                    chain = %self.0.chain,
+                    url = %endpoint.url,
+                    "upstream request failed, not failing over a request that can't be repeated"
+                );
+                return Err(err);
+            }
+            tracing::warn!(
+                ?err,
+                chain = %self.0.chain,
+                url = %endpoint.url,
+                "upstream request failed, failing over"
+            );
+            last_err = Some(err);
+        }
+        Err(last_err.unwrap_or_else(TransportErrorKind::backend_gone))
+    }
+}
+
+impl Service<RequestPacket> for Upstreams {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().send(req))
+    }
+}
+
+impl Inner {
+    /// Healthy upstreams in order, followed by the unhealthy ones as a last resort
+    fn candidates(&self) -> impl Iterator<Item = &Endpoint> {
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) =
+            self.endpoints.iter().partition(|e| e.is_healthy());
+        healthy.into_iter().chain(unhealthy)
+    }
+
+    async fn run_probes(this: Weak<Self>) {
+        let mut interval = tokio::time::interval(PROBE_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // the chain is gone once nothing uses its upstreams anymore
+            let Some(this) = this.upgrade() else {
+                break;
+            };
+            this.probe().await;
+        }
+    }
+
+    /// Connects the upstreams that are down, then fetches the head of every upstream and
+    /// updates their health
+    async fn probe(&self) {
+        let heads = futures::future::join_all(
+            self.endpoints
+                .iter()
+                .map(|endpoint| endpoint.probe(self.chain)),
+        )
+        .await;
+
+        let best = heads.iter().flatten().max().copied();
+        if best.is_none() {
+            tracing::warn!(chain = %self.chain, "no upstream is reachable");
+        }
+        let blocktime = self.chain.average_blocktime_hint();
+        let max_lag = blocktime
+            .map(|blocktime| (MAX_HEAD_LAG.as_secs_f64() / blocktime.as_secs_f64()) as u64)
+            .unwrap_or_default()
+            .max(MIN_BLOCK_LAG);
+        // chains without a known block time, like dev chains, may not produce blocks for a while
+        let stall_timeout =
+            blocktime.map(|blocktime| (blocktime * STALL_BLOCKS).max(MIN_STALL_TIMEOUT));
+
+        let now = Instant::now();
+        for (endpoint, head) in self.endpoints.iter().zip(heads) {
+            let mut health = endpoint.w_health();
+            let Some(head) = head else {
+                health.healthy = false;
+                continue;
+            };
+            if head > health.head || health.advanced_at.is_none() {
+                health.head = head;
+                health.advanced_at = Some(now);
+            }
+            let lagging = best.is_some_and(|best| best - head > max_lag);
+            let stalled = stall_timeout
+                .zip(health.advanced_at)
+                .is_some_and(|(timeout, advanced_at)| now.duration_since(advanced_at) > timeout);
+            let healthy = !lagging && !stalled;
+            if health.healthy != healthy {
+                tracing::info!(
+                    chain = %self.chain,
+                    url = %endpoint.url,
+                    head,
+                    lagging,
+                    stalled,
+                    healthy,
+                    "upstream health changed"
+                );
+            }
+            health.healthy = healthy;
+        }
+    }
+}
+
+impl Endpoint {
+    fn is_healthy(&self) -> bool {
+        self.r_health().healthy
+    }
+
+    fn provider(&self) -> Option<RootProvider> {
+        self.r_provider().clone()
+    }
+
+    fn mark_unhealthy(&self, err: &TransportError) {
+        self.w_health().healthy = false;
+        // the connection can't be used anymore, it's reopened by the next probe
+        if let RpcError::Transport(kind) = err
+            && kind.is_backend_gone()
+        {
+            self.w_provider().take();
+        }
+    }
+
+    /// Returns the head of the upstream, connecting to it first if needed
+    async fn probe(&self, chain: NamedChain) -> Option<u64> {
+        if self.r_health().wrong_chain {
+            return None;
+        }
+        let provider = match self.provider() {
+            Some(provider) => provider,
+            None => self.connect(chain).await?,
+        };
+        tokio::time::timeout(PROBE_TIMEOUT, provider.get_block_number())
+            .await
+            .map_err(|_| TransportErrorKind::custom_str("health probe timed out"))
+            .flatten()
+            .inspect_err(
+                |err| tracing::debug!(?err, %chain, url = %self.url, "health probe failed"),
+            )
+            .ok()
+    }
+
+    async fn connect(&self, chain: NamedChain) -> Option<RootProvider> {
+        let connect = async {
+            let provider = RootProvider::connect(self.url.as_str()).await?;
+            let chain_id = provider.get_chain_id().await?;
+            Ok::<_, TransportError>((provider, chain_id))
+        };
+        let (provider, chain_id) = match tokio::time::timeout(PROBE_TIMEOUT, connect).await {
+            Ok(Ok(connected)) => connected,
+            Ok(Err(err)) => {
+                tracing::warn!(?err, %chain, url = %self.url, "error connecting to the rpc");
+                return None;
+            }
+            Err(_) => {
+                tracing::warn!(%chain, url = %self.url, "timed out connecting to the rpc");
+                return None;
+            }
+        };
+        if chain_id != chain as u64 {
+            tracing::error!(%chain, url = %self.url, chain_id, "rpc serves another chain");
+            self.w_health().wrong_chain = true;
+            return None;
+        }
+        *self.w_provider() = Some(provider.clone());
+        Some(provider)
+    }
+
+    fn r_health(&self) -> RwLockReadGuard<'_, Health> {
+        self.health
+            .read()
+            .expect("failed to get read lock on upstream health")
+    }
+
+    fn w_health(&self) -> RwLockWriteGuard<'_, Health> {
+        self.health
+            .write()
+            .expect("failed to get write lock on upstream health")
+    }
+
+    fn r_provider(&self) -> RwLockReadGuard<'_, Option<RootProvider>> {
+        self.provider
+            .read()
+            .expect("failed to get read lock on upstream provider")
+    }
+
+    fn w_provider(&self) -> RwLockWriteGuard<'_, Option<RootProvider>> {
+        self.provider
+            .write()
+            .expect("failed to get write lock on upstream provider")
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    future::poll_fn,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
+
+use alloy::hex;
+use alloy_chains::NamedChain;
+use bytes::Bytes;
+use h3::{
+    ext::Protocol, frame::FrameStream, proto::frame::Frame, quic::StreamId, server::RequestStream,
+    stream::BufRecvStream,
+};
+use hyper::{Method, Response, StatusCode, header::HeaderValue};
+use jsonrpsee::server::StopHandle;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::rpc::{PerConnection, chain_id_or_name_to_named_chain};
+
+/// Browsers only accept a certificate pinned by its hash if it's valid for at most two weeks
+const CERTIFICATE_VALIDITY: Duration = Duration::from_secs(13 * 24 * 60 * 60);
+/// The certificate is replaced a day before it expires, open connections keep the old one
+const CERTIFICATE_ROTATION: Duration = Duration::from_secs(12 * 24 * 60 * 60);
+/// Keeps idle connections, like the one of the browser extension, from timing out
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Header of the response accepting a WebTransport session, required by Chromium
+const WEBTRANSPORT_DRAFT_HEADER: &str = "sec-webtransport-http3-draft";
+
+/// Where and how to reach the WebTransport endpoint, served on `/webtransport` so that clients
+/// can pin the certificate of the endpoint by its hash, e.g. with the `serverCertificateHashes`
+/// option of the browser's `WebTransport`.
+#[derive(Clone, Debug)]
+pub struct WebTransportInfo {
+    port: u16,
+    certificate_hash: Arc<RwLock<[u8; 32]>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebTransportInfoJson {
+    port: u16,
+    /// Hex encoded SHA-256 hash of the DER encoded certificate
+    certificate_hash: String,
+}
+
+impl WebTransportInfo {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// SHA-256 hash of the current certificate
+    pub fn certificate_hash(&self) -> [u8; 32] {
+        *self.r_certificate_hash()
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(&WebTransportInfoJson {
+            port: self.port,
+            certificate_hash: hex::encode(self.certificate_hash()),
+        })
+        .expect("webtransport info serializes")
+    }
+
+    fn r_certificate_hash(&self) -> RwLockReadGuard<'_, [u8; 32]> {
+        self.certificate_hash
+            .read()
+            .expect("failed to get read lock on certificate hash")
+    }
+
+    fn w_certificate_hash(&self) -> RwLockWriteGuard<'_, [u8; 32]> {
+        self.certificate_hash
+            .write()
+            .expect("failed to get write lock on certificate hash")
+    }
+}
+
+/// Self-signed ECDSA certificate of the endpoint
+struct Certificate {
+    der: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+}
+
+impl Certificate {
+    fn generate() -> eyre::Result<Self> {
+        let key = rcgen::KeyPair::generate()?;
+        let mut params = rcgen::CertificateParams::new(vec![
+            "localhost".to_string(),
+            "127.0.0.1".to_string(),
+            "::1".to_string(),
+        ])?;
+        let now = time::OffsetDateTime::now_utc();
+        params.not_before = now - time::Duration::minutes(5);
+        params.not_after = now + CERTIFICATE_VALIDITY;
+        let cert = params.self_signed(&key)?;
+        Ok(Self {
+            der: cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(key.serialize_der()),
+        })
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        Sha256::digest(&self.der).into()
+    }
+
+    fn server_config(&self) -> eyre::Result<quinn::ServerConfig> {
+        let mut tls = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![self.der.clone()],
+            PrivateKeyDer::Pkcs8(self.key.clone_key()),
+        )?;
+        tls.alpn_protocols = vec![b"h3".to_vec()];
+
+        let mut config = quinn::ServerConfig::with_crypto(Arc::new(
+            quinn::crypto::rustls::QuicServerConfig::try_from(tls)?,
+        ));
+        let mut transport = quinn::TransportConfig::default();
+        transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+        config.transport_config(Arc::new(transport));
+        Ok(config)
+    }
+}
+
+/// WebTransport over HTTP/3 endpoints, one per address the server listens on.
+///
+/// A client opens a session with an extended CONNECT to the path of the chain, e.g.
+/// `https://127.0.0.1:1249/mainnet`, then speaks raw JSON-RPC on bidirectional streams of the
+/// session. Each stream gets its own session on the server, like a WebSocket connection.
+pub(crate) struct WebTransportServer {
+    endpoints: Vec<quinn::Endpoint>,
+    info: WebTransportInfo,
+}
+
+impl WebTransportServer {
+    pub(crate) fn bind(hosts: &[IpAddr], port: u16) -> eyre::Result<Self> {
+        let certificate = Certificate::generate()?;
+        let config = certificate.server_config()?;
+        let endpoints = hosts
+            .iter()
+            .map(|host| quinn::Endpoint::server(config.clone(), SocketAddr::new(*host, port)))
+            .collect::<Result<Vec<_>, _>>()?;
+        tracing::info!(
+            port,
+            certificate_hash = %hex::encode(certificate.hash()),
+            "serving WebTransport"
+        );
+        Ok(Self {
+            endpoints,
+            info: WebTransportInfo {
+                port,
+                certificate_hash: Arc::new(RwLock::new(certificate.hash())),
+            },
+        })
+    }
+
+    pub(crate) fn info(&self) -> &WebTransportInfo {
+        &self.info
+    }
+
+    /// Accepts connections until the server stops
+    pub(crate) fn spawn(self, per_conn: PerConnection) {
+        tokio::spawn(rotate_certificates(
+            self.endpoints.clone(),
+            self.info.clone(),
+            per_conn.stop_handle(),
+        ));
+        for endpoint in self.endpoints {
+            let per_conn = per_conn.clone();
+            tokio::spawn(async move {
+                loop {
+                    let incoming = tokio::select! {
+                        incoming = endpoint.accept() => match incoming {
+                            Some(incoming) => incoming,
+                            None => break,
+                        },
+                        _ = per_conn.stop_handle().shutdown() => break,
+                    };
+                    let per_conn = per_conn.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = serve_connection(per_conn, incoming).await {
+                            tracing::debug!(?err, "webtransport connection closed with an error");
+                        }
+                    });
+                }
+                endpoint.close(0u32.into(), b"server stopped");
+            });
+        }
+    }
+}
+
+async fn rotate_certificates(
+    endpoints: Vec<quinn::Endpoint>,
+    info: WebTransportInfo,
+    stop_handle: StopHandle,
+) {
+    let mut interval = tokio::time::interval(CERTIFICATE_ROTATION);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stop_handle.clone().shutdown() => break,
+        }
+        let rotated = Certificate::generate().and_then(|certificate| {
+            let config = certificate.server_config()?;
+            for endpoint in &endpoints {
+                endpoint.set_server_config(Some(config.clone()));
+            }
+            Ok(certificate.hash())
+        });
+        match rotated {
+            Ok(hash) => {
+                *info.w_certificate_hash() = hash;
+                tracing::info!(hash = %hex::encode(hash), "rotated webtransport certificate");
+            }
+            Err(err) => tracing::error!(?err, "error rotating webtransport certificate"),
+        }
+    }
+}
+
+/// A WebTransport session, it lasts as long as the stream of its CONNECT request stays open
+struct Session {
+    connect: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    chain: NamedChain,
+    origin: Option<HeaderValue>,
+}
+
+/// Serves a single WebTransport session on the connection, plain HTTP/3 requests are refused
+async fn serve_connection(per_conn: PerConnection, incoming: quinn::Incoming) -> eyre::Result<()> {
+    let conn = incoming.await?;
+    let mut conn = h3::server::builder()
+        .enable_webtransport(true)
+        .enable_extended_connect(true)
+        .enable_datagram(true)
+        .max_webtransport_sessions(1)
+        .send_grease(true)
+        .build::<_, Bytes>(h3_quinn::Connection::new(conn))
+        .await?;
+
+    let mut session: Option<Session> = None;
+    while let Some(stream) = poll_fn(|cx| conn.poll_accept_request_stream(cx)).await? {
+        let mut stream = FrameStream::new(BufRecvStream::new(stream));
+        let frame = poll_fn(|cx| stream.poll_next(cx)).await;
+        if let Ok(Some(Frame::WebTransportStream(session_id))) = frame {
+            match &session {
+                Some(session) if StreamId::from(session_id) == session.connect.id() => {
+                    let per_conn = per_conn.clone();
+                    let (chain, origin) = (session.chain, session.origin.clone());
+                    tokio::spawn(async move {
+                        if let Err(err) =
+                            per_conn.serve_raw(stream.into_inner(), chain, origin).await
+                        {
+                            tracing::debug!(?err, "webtransport stream closed with an error");
+                        }
+                    });
+                }
+                _ => tracing::debug!(?session_id, "stream of an unknown webtransport session"),
+            }
+            continue;
+        }
+
+        let (req, mut stream) = conn
+            .create_resolver(stream)
+            .accept_with_frame(frame)?
+            .resolve()
+            .await?;
+        if session.is_some()
+            || req.method() != Method::CONNECT
+            || req.extensions().get::<Protocol>() != Some(&Protocol::WEB_TRANSPORT)
+        {
+            stream
+                .send_response(status_response(StatusCode::BAD_REQUEST))
+                .await?;
+            stream.finish().await?;
+            continue;
+        }
+
+        let origin = match per_conn.check_access(&req) {
+            Ok(origin) => origin,
+            Err(rejection) => {
+                let (parts, _) = rejection.into_response().into_parts();
+                stream
+                    .send_response(Response::from_parts(parts, ()))
+                    .await?;
+                stream.finish().await?;
+                continue;
+            }
+        };
+        let chain = chain_id_or_name_to_named_chain(req.uri().path().trim_start_matches('/'))
+            .ok()
+            .filter(|chain| per_conn.serves(chain));
+        let Some(chain) = chain else {
+            stream
+                .send_response(status_response(StatusCode::NOT_FOUND))
+                .await?;
+            stream.finish().await?;
+            continue;
+        };
+
+        let mut rp = status_response(StatusCode::OK);
+        rp.headers_mut().insert(
+            WEBTRANSPORT_DRAFT_HEADER,
+            HeaderValue::from_static("draft02"),
+        );
+        stream.send_response(rp).await?;
+        tracing::info!(%chain, ?origin, "Opened WebTransport session");
+        session = Some(Session {
+            connect: stream,
+            chain,
+            origin,
+        });
+    }
+    Ok(())
+}
+
+fn status_response(status: StatusCode) -> Response<()> {
+    let mut rp = Response::new(());
+    *rp.status_mut() = status;
+    rp
+}
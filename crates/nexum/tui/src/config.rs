@@ -10,20 +10,68 @@ use figment::{
     Figment,
     providers::{Format, Toml},
 };
-use serde::{Deserialize, Serialize};
+use nexum_rpc::{
+    access::{DEFAULT_ALLOWED_HOSTS, DEFAULT_ALLOWED_ORIGINS},
+    fees::{FeeSpeed, FeeStrategy},
+    rpc::TokenMetadata,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 use url::Url;
 
 use crate::signers::{NexumAccount, load_keystores};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
-    pub rpcs: BTreeMap<String, Url>,
+    /// Upstream rpcs of each chain in order of preference
+    #[serde(deserialize_with = "deserialize_rpcs")]
+    pub rpcs: BTreeMap<String, Vec<Url>>,
     #[serde(default)]
     pub origin_connections: BTreeMap<Address, HashMap<Url, bool>>,
     #[serde(default)]
     pub labels: BTreeMap<NamedChain, HashMap<Address, String>>,
     #[serde(default)]
+    pub tokens: BTreeMap<NamedChain, HashMap<Address, TokenMetadata>>,
+    /// How the fees of the transactions of each chain are priced, chains missing from it bid the
+    /// median priority fee
+    #[serde(default = "default_fees")]
+    pub fees: BTreeMap<NamedChain, FeeStrategy>,
+    #[serde(default)]
     pub signer: SignerConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+}
+
+/// Who may connect to the local rpc server, and how
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Host names the server may be reached by, anything else is refused to defeat DNS
+    /// rebinding
+    pub allowed_hosts: Vec<String>,
+    /// Browser origins allowed to connect, e.g. `https://app.example.com`, `*` is a wildcard.
+    /// Browser extensions only matched by a wildcard must send the token, list the exact origin
+    /// of the wallet's extension to let it connect without one.
+    pub allowed_origins: Vec<String>,
+    /// Bearer token the clients must send, either in the `Authorization` header or in the
+    /// `token` query parameter
+    pub token: Option<String>,
+    /// Path of the Unix domain socket serving raw JSON-RPC, only the current user may connect
+    pub ipc_path: Option<PathBuf>,
+    /// UDP port serving JSON-RPC over WebTransport, with a self-signed certificate that clients
+    /// pin by the hash served on `/webtransport`
+    pub webtransport_port: Option<u16>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: DEFAULT_ALLOWED_HOSTS.map(String::from).to_vec(),
+            allowed_origins: DEFAULT_ALLOWED_ORIGINS.map(String::from).to_vec(),
+            token: None,
+            ipc_path: None,
+            webtransport_port: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -51,38 +99,91 @@ impl Default for LedgerConfig {
     }
 }
 
+/// Fee strategies of the chains served by default, testnet transactions don't need to be fast
+fn default_fees() -> BTreeMap<NamedChain, FeeStrategy> {
+    let slow = FeeStrategy {
+        speed: FeeSpeed::Slow,
+        ..Default::default()
+    };
+    BTreeMap::from([
+        (NamedChain::Mainnet, FeeStrategy::default()),
+        (
+            NamedChain::Gnosis,
+            FeeStrategy {
+                // validators skip transactions tipping less than 1 gwei
+                min_priority_fee: Some(1_000_000_000),
+                ..Default::default()
+            },
+        ),
+        (NamedChain::Sepolia, slow),
+        (NamedChain::Holesky, slow),
+        (NamedChain::Hoodi, slow),
+    ])
+}
+
+/// Reads the rpcs of each chain, either a single url as in older configs or a list of urls
+fn deserialize_rpcs<'de, D>(deserializer: D) -> Result<BTreeMap<String, Vec<Url>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Rpcs {
+        One(Url),
+        Many(Vec<Url>),
+    }
+
+    Ok(BTreeMap::<String, Rpcs>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(chain, rpcs)| match rpcs {
+            Rpcs::One(url) => (chain, vec![url]),
+            Rpcs::Many(urls) => (chain, urls),
+        })
+        .collect())
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             rpcs: BTreeMap::from([
                 (
                     "Mainnet".to_string(),
-                    "https://eth.llamarpc.com".parse().unwrap(),
+                    vec![
+                        "https://eth.llamarpc.com".parse().unwrap(),
+                        "https://ethereum-rpc.publicnode.com".parse().unwrap(),
+                    ],
                 ),
                 (
                     "Gnosis".to_string(),
-                    "https://rpc.gnosischain.com".parse().unwrap(),
+                    vec!["https://rpc.gnosischain.com".parse().unwrap()],
                 ),
                 (
                     "Sepolia".to_string(),
-                    "https://ethereum-sepolia-rpc.publicnode.com"
-                        .parse()
-                        .unwrap(),
+                    vec![
+                        "https://ethereum-sepolia-rpc.publicnode.com"
+                            .parse()
+                            .unwrap(),
+                    ],
                 ),
                 (
                     "Holesky".to_string(),
-                    "https://ethereum-holesky-rpc.publicnode.com"
-                        .parse()
-                        .unwrap(),
+                    vec![
+                        "https://ethereum-holesky-rpc.publicnode.com"
+                            .parse()
+                            .unwrap(),
+                    ],
                 ),
                 (
                     "Hoodi".to_string(),
-                    "https://rpc.hoodi.ethpandaops.io".parse().unwrap(),
+                    vec!["https://rpc.hoodi.ethpandaops.io".parse().unwrap()],
                 ),
             ]),
             origin_connections: BTreeMap::new(),
             labels: BTreeMap::new(),
+            tokens: BTreeMap::new(),
+            fees: default_fees(),
             signer: SignerConfig::default(),
+            server: ServerConfig::default(),
         }
     }
 }
@@ -90,13 +191,13 @@ impl Default for Config {
 impl Config {
     /// Returns chain RPCs parsed from config keys (no network validation).
     /// Invalid chain names are skipped with a warning.
-    pub fn chain_rpcs(&self) -> Vec<(NamedChain, Url)> {
+    pub fn chain_rpcs(&self) -> Vec<(NamedChain, Vec<Url>)> {
         self.rpcs
             .iter()
-            .filter_map(|(chain_name, url)| {
+            .filter_map(|(chain_name, urls)| {
                 chain_name
                     .parse::<NamedChain>()
-                    .map(|chain| (chain, url.clone()))
+                    .map(|chain| (chain, urls.clone()))
                     .inspect_err(|e| {
                         tracing::warn!(chain_name, ?e, "failed to parse chain name, skipping");
                     })
@@ -105,6 +206,76 @@ impl Config {
             .collect()
     }
 
+    /// Sets the upstream rpcs of the chain, replacing the ones it had
+    pub fn set_chain_rpcs(&mut self, chain: NamedChain, urls: Vec<Url>) {
+        let key = self
+            .chain_rpcs_key(&chain)
+            .unwrap_or_else(|| chain.to_string());
+        self.rpcs.insert(key, urls);
+    }
+
+    /// Removes the upstream rpcs of the chain. Returns false if it had none.
+    pub fn remove_chain_rpcs(&mut self, chain: &NamedChain) -> bool {
+        self.chain_rpcs_key(chain)
+            .and_then(|key| self.rpcs.remove(&key))
+            .is_some()
+    }
+
+    /// Returns the key of the chain's rpcs, chains may be named in any case
+    fn chain_rpcs_key(&self, chain: &NamedChain) -> Option<String> {
+        self.rpcs
+            .keys()
+            .find(|key| key.parse::<NamedChain>().ok() == Some(*chain))
+            .cloned()
+    }
+
+    /// Fee strategy of each chain
+    pub fn fee_strategies(&self) -> HashMap<NamedChain, FeeStrategy> {
+        self.fees
+            .iter()
+            .map(|(chain, strategy)| (*chain, *strategy))
+            .collect()
+    }
+
+    /// Returns whether the account has been granted to the origin
+    pub fn is_origin_connected(&self, account: &Address, origin: &Url) -> bool {
+        self.origin_connections
+            .get(account)
+            .and_then(|origins| origins.get(origin))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns all the accounts that have been granted to the origin
+    pub fn origin_accounts(&self, origin: &Url) -> Vec<Address> {
+        self.origin_connections
+            .iter()
+            .filter(|(_, origins)| origins.get(origin).copied().unwrap_or_default())
+            .map(|(account, _)| *account)
+            .collect()
+    }
+
+    pub fn set_origin_connection(&mut self, account: Address, origin: Url, allowed: bool) {
+        self.origin_connections
+            .entry(account)
+            .or_default()
+            .insert(origin, allowed);
+    }
+
+    /// Returns the metadata of the token if it's tracked on the chain
+    pub fn token(&self, chain: &NamedChain, address: &Address) -> Option<&TokenMetadata> {
+        self.tokens
+            .get(chain)
+            .and_then(|tokens| tokens.get(address))
+    }
+
+    pub fn track_token(&mut self, chain: NamedChain, address: Address, metadata: TokenMetadata) {
+        self.tokens
+            .entry(chain)
+            .or_default()
+            .insert(address, metadata);
+    }
+
     pub fn keystores(&self) -> eyre::Result<Vec<NexumAccount>> {
         Ok(self
             .signer
@@ -133,14 +304,24 @@ pub fn config_dir() -> eyre::Result<PathBuf> {
     Ok(dir)
 }
 
+/// Returns the path of the config file, `nxm.toml` in the config directory
+pub fn config_path() -> eyre::Result<PathBuf> {
+    Ok(config_dir()?.join("nxm.toml"))
+}
+
+/// Writes the config to `nxm.toml` in the config directory
+pub fn save_config(config: &Config) -> eyre::Result<()> {
+    std::fs::write(config_path()?, toml::to_string_pretty(config)?)?;
+    Ok(())
+}
+
+/// Reads the config file, failing if it's missing or invalid
+pub fn read_config() -> eyre::Result<Config> {
+    Ok(Figment::new()
+        .merge(Toml::file_exact(config_path()?))
+        .extract()?)
+}
+
 pub fn load_config() -> Config {
-    config_dir()
-        .ok()
-        .and_then(|dir| {
-            Figment::new()
-                .merge(Toml::file(dir.join("nxm.toml")))
-                .extract()
-                .ok()
-        })
-        .unwrap_or_default()
+    read_config().unwrap_or_default()
 }
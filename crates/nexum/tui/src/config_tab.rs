@@ -1,22 +1,52 @@
-use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
 use alloy::primitives::Address;
 use alloy_chains::NamedChain;
 use crossterm::event::{KeyCode, KeyEvent};
+use eyre::OptionExt;
+use nexum_rpc::{fees::FeeStrategy, rpc::chain_id_or_name_to_named_chain};
 use ratatui::{
     layout::{Constraint, Layout},
     prelude::{Buffer, Rect},
-    style::Style,
-    widgets::{Block, List, ListState, Padding, Row, StatefulWidget, Table, Widget},
+    style::{Color, Style},
+    widgets::{Block, List, ListState, Padding, Paragraph, Row, StatefulWidget, Table, Widget},
 };
+use tokio::sync::watch;
+use url::Url;
 
-use crate::{HandleEvent, config::Config};
+use crate::{
+    HandleEvent,
+    config::{Config, save_config},
+};
 
 pub struct ConfigTab {
-    pub config: Config,
+    config: RwLock<Config>,
+    /// Upstream rpcs of each chain in the config, watched to serve the chains as they change
+    rpcs: watch::Sender<Vec<(NamedChain, Vec<Url>)>>,
+    /// Fee strategy of each chain in the config, watched to price the transactions as it changes
+    fee_strategies: watch::Sender<HashMap<NamedChain, FeeStrategy>>,
     config_list_state: Mutex<ListState>,
     origin_connections_collapsed: RwLock<bool>,
     labels_collapsed: RwLock<bool>,
+    tokens_collapsed: RwLock<bool>,
+    rpc_edit: RwLock<Option<RpcEdit>>,
+}
+
+/// Change of the rpcs being typed in by the user
+struct RpcEdit {
+    kind: RpcEditKind,
+    input: String,
+    error: Option<String>,
+}
+
+enum RpcEditKind {
+    /// Adds a chain or replaces its rpcs, typed as `<chain>=<url>[,<url>...]`
+    Set,
+    /// Removes a chain, typed as its name or id
+    Remove,
 }
 
 #[derive(Debug)]
@@ -26,6 +56,8 @@ enum ConfigListItemType {
     OriginConnectionsMeta,
     Labels(NamedChain),
     LabelsMeta,
+    Tokens(NamedChain),
+    TokensMeta,
     Meta,
 }
 
@@ -35,22 +67,127 @@ impl ConfigTab {
         list_state.select_first();
 
         Self {
-            config,
+            rpcs: watch::Sender::new(config.chain_rpcs()),
+            fee_strategies: watch::Sender::new(config.fee_strategies()),
+            config: config.into(),
             config_list_state: Mutex::new(list_state),
             origin_connections_collapsed: false.into(),
             labels_collapsed: false.into(),
+            tokens_collapsed: false.into(),
+            rpc_edit: None.into(),
+        }
+    }
+
+    /// Applies the update to the config and persists it to `nxm.toml`
+    pub fn update_config(&self, update: impl FnOnce(&mut Config)) -> eyre::Result<()> {
+        let mut config = self.w_config();
+        update(&mut config);
+        self.publish(&config);
+        save_config(&config)
+    }
+
+    /// Replaces the config with the one read from `nxm.toml`, e.g. after it's been edited by hand
+    pub fn reload(&self, config: Config) {
+        self.publish(&config);
+        *self.w_config() = config;
+    }
+
+    /// Watches the upstream rpcs of each chain in the config
+    pub fn subscribe_rpcs(&self) -> watch::Receiver<Vec<(NamedChain, Vec<Url>)>> {
+        self.rpcs.subscribe()
+    }
+
+    /// Watches the fee strategy of each chain in the config
+    pub fn subscribe_fee_strategies(&self) -> watch::Receiver<HashMap<NamedChain, FeeStrategy>> {
+        self.fee_strategies.subscribe()
+    }
+
+    /// Returns whether the user is typing in a change, which takes all the keys
+    pub fn is_editing(&self) -> bool {
+        self.r_rpc_edit().is_some()
+    }
+
+    fn publish(&self, config: &Config) {
+        let rpcs = config.chain_rpcs();
+        self.rpcs.send_if_modified(|current| {
+            let modified = *current != rpcs;
+            *current = rpcs;
+            modified
+        });
+        let fee_strategies = config.fee_strategies();
+        self.fee_strategies.send_if_modified(|current| {
+            let modified = *current != fee_strategies;
+            *current = fee_strategies;
+            modified
+        });
+    }
+
+    fn start_rpc_edit(&self, kind: RpcEditKind) {
+        *self.w_rpc_edit() = Some(RpcEdit {
+            kind,
+            input: String::new(),
+            error: None,
+        });
+    }
+
+    /// Applies the change typed in by the user, keeping it open to fix it if it's invalid
+    fn apply_rpc_edit(&self) {
+        let mut rpc_edit = self.w_rpc_edit();
+        let Some(edit) = rpc_edit.as_mut() else {
+            return;
+        };
+        let applied = match edit.kind {
+            RpcEditKind::Set => parse_chain_rpcs(&edit.input).and_then(|(chain, urls)| {
+                self.update_config(|config| config.set_chain_rpcs(chain, urls))
+            }),
+            RpcEditKind::Remove => {
+                chain_id_or_name_to_named_chain(edit.input.trim()).and_then(|chain| {
+                    let mut removed = false;
+                    self.update_config(|config| removed = config.remove_chain_rpcs(&chain))?;
+                    if !removed {
+                        eyre::bail!("{chain} has no rpcs");
+                    }
+                    Ok(())
+                })
+            }
+        };
+        match applied {
+            Ok(()) => *rpc_edit = None,
+            Err(err) => edit.error = Some(err.to_string()),
+        }
+    }
+
+    fn handle_rpc_edit_key(&self, event: &KeyEvent) {
+        match event.code {
+            KeyCode::Esc => *self.w_rpc_edit() = None,
+            KeyCode::Enter => self.apply_rpc_edit(),
+            KeyCode::Backspace => {
+                if let Some(edit) = self.w_rpc_edit().as_mut() {
+                    edit.input.pop();
+                }
+            }
+            KeyCode::Char(ch) => {
+                if let Some(edit) = self.w_rpc_edit().as_mut() {
+                    edit.input.push(ch);
+                }
+            }
+            _ => {}
         }
     }
 
     fn list_len(&self) -> usize {
-        3 + if *self.r_origin_connections_collapsed() {
+        4 + if *self.r_origin_connections_collapsed() {
             0
         } else {
-            self.config.origin_connections.len()
+            self.r_config().origin_connections.len()
         } + if *self.r_labels_collapsed() {
             0
         } else {
-            self.config.labels.len()
+            self.r_config().labels.len()
+        } + if *self.r_tokens_collapsed() {
+            0
+        } else {
+            self.r_config().tokens.len()
         }
     }
 
@@ -62,11 +199,22 @@ impl ConfigTab {
         2 + if *self.r_origin_connections_collapsed() {
             0
         } else {
-            self.config.origin_connections.len()
+            self.r_config().origin_connections.len()
         }
     }
 
+    fn tokens_offset(&self) -> usize {
+        self.labels_offset()
+            + 1
+            + if *self.r_labels_collapsed() {
+                0
+            } else {
+                self.r_config().labels.len()
+            }
+    }
+
     fn item_at(&self, idx: usize) -> ConfigListItemType {
+        let tokens_offset = self.tokens_offset();
         let labels_offset = self.labels_offset();
         let origin_connections_offset = self.origin_connections_offset();
         let list_len = self.list_len();
@@ -75,22 +223,31 @@ impl ConfigTab {
             0 => ConfigListItemType::Rpcs,
             idx if idx == origin_connections_offset => ConfigListItemType::OriginConnectionsMeta,
             idx if idx == labels_offset => ConfigListItemType::LabelsMeta,
+            idx if idx == tokens_offset => ConfigListItemType::TokensMeta,
             idx if idx < labels_offset => ConfigListItemType::OriginConnections(
                 *self
-                    .config
+                    .r_config()
                     .origin_connections
                     .keys()
                     .nth(idx - origin_connections_offset - 1)
                     .expect("idx is out of bounds"),
             ),
-            idx if idx < list_len => ConfigListItemType::Labels(
+            idx if idx < tokens_offset => ConfigListItemType::Labels(
                 *self
-                    .config
+                    .r_config()
                     .labels
                     .keys()
                     .nth(idx - labels_offset - 1)
                     .expect("idx is out of bounds"),
             ),
+            idx if idx < list_len => ConfigListItemType::Tokens(
+                *self
+                    .r_config()
+                    .tokens
+                    .keys()
+                    .nth(idx - tokens_offset - 1)
+                    .expect("idx is out of bounds"),
+            ),
             _ => ConfigListItemType::Meta,
         }
     }
@@ -127,6 +284,30 @@ impl ConfigTab {
         }
     }
 
+    pub fn r_config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config
+            .read()
+            .expect("failed to get read lock on config")
+    }
+
+    fn w_config(&self) -> RwLockWriteGuard<'_, Config> {
+        self.config
+            .write()
+            .expect("failed to get write lock on config")
+    }
+
+    fn r_rpc_edit(&self) -> RwLockReadGuard<'_, Option<RpcEdit>> {
+        self.rpc_edit
+            .read()
+            .expect("failed to get read lock on rpc_edit")
+    }
+
+    fn w_rpc_edit(&self) -> RwLockWriteGuard<'_, Option<RpcEdit>> {
+        self.rpc_edit
+            .write()
+            .expect("failed to get write lock on rpc_edit")
+    }
+
     fn r_origin_connections_collapsed(&self) -> RwLockReadGuard<'_, bool> {
         self.origin_connections_collapsed
             .read()
@@ -150,6 +331,18 @@ impl ConfigTab {
             .write()
             .expect("failed to get write lock on labels_collapsed")
     }
+
+    fn r_tokens_collapsed(&self) -> RwLockReadGuard<'_, bool> {
+        self.tokens_collapsed
+            .read()
+            .expect("failed to get read lock on tokens_collapsed")
+    }
+
+    fn w_tokens_collapsed(&self) -> RwLockWriteGuard<'_, bool> {
+        self.tokens_collapsed
+            .write()
+            .expect("failed to get write lock on tokens_collapsed")
+    }
 }
 
 impl Widget for &ConfigTab {
@@ -157,15 +350,17 @@ impl Widget for &ConfigTab {
     where
         Self: Sized,
     {
-        let mut list_items =
-            Vec::with_capacity(3 + self.config.origin_connections.len() + self.config.labels.len());
+        let config = self.r_config();
+        let mut list_items = Vec::with_capacity(
+            4 + config.origin_connections.len() + config.labels.len() + config.tokens.len(),
+        );
         list_items.push("RPCs".to_string());
 
         if *self.r_origin_connections_collapsed() {
             list_items.push("▶ Origin Connections".to_string())
         } else {
             list_items.push("▼ Origin Connections".to_string());
-            for addr in self.config.origin_connections.keys() {
+            for addr in config.origin_connections.keys() {
                 list_items.push(format!("  {addr}"));
             }
         }
@@ -174,10 +369,21 @@ impl Widget for &ConfigTab {
             list_items.push("▶ Labels".to_string())
         } else {
             list_items.push("▼ Labels".to_string());
-            for chain in self.config.labels.keys() {
+            for chain in config.labels.keys() {
+                list_items.push(format!("  {chain}"));
+            }
+        }
+
+        if *self.r_tokens_collapsed() {
+            list_items.push("▶ Tokens".to_string())
+        } else {
+            list_items.push("▼ Tokens".to_string());
+            for chain in config.tokens.keys() {
                 list_items.push(format!("  {chain}"));
             }
         }
+        // the helpers below take their own read lock
+        drop(config);
         assert!(
             list_items.len() == self.list_len(),
             "list_items.len() is wrong"
@@ -210,29 +416,58 @@ impl Widget for &ConfigTab {
             match item {
                 ConfigListItemType::Rpcs => {
                     let table = Table::new(
-                        self.config
+                        self.r_config()
                             .rpcs
                             .iter()
-                            .map(|(k, v)| Row::new(vec![k.to_owned(), v.to_string()]))
+                            .map(|(k, v)| {
+                                let urls = v.iter().map(Url::as_str).collect::<Vec<_>>();
+                                Row::new(vec![k.to_owned(), urls.join(", ")])
+                            })
                             .collect::<Vec<_>>(),
                         vec![Constraint::Percentage(20), Constraint::Percentage(80)],
                     )
                     .column_spacing(1)
                     .header(
-                        Row::new(vec!["Name", "URL"])
+                        Row::new(vec!["Name", "URLs"])
                             .style(Style::default().bold())
                             .bottom_margin(1),
                     )
                     .block(
                         Block::bordered()
                             .title("RPCs")
+                            .title_bottom("[a]dd or replace ───── [d]elete")
                             .padding(Padding::new(1, 0, 0, 0)),
                     );
-                    Widget::render(table, right_area, buf);
+                    match &*self.r_rpc_edit() {
+                        Some(edit) => {
+                            let [table_area, input_area] =
+                                Layout::vertical(vec![Constraint::Fill(1), Constraint::Length(3)])
+                                    .areas(right_area);
+                            Widget::render(table, table_area, buf);
+                            let title = match edit.kind {
+                                RpcEditKind::Set => " <chain>=<url>[,<url>...] ",
+                                RpcEditKind::Remove => " Chain to remove ",
+                            };
+                            let mut block = Block::bordered()
+                                .title(title)
+                                .border_style(Style::default().fg(Color::Blue));
+                            if let Some(error) = &edit.error {
+                                block = block
+                                    .title_bottom(format!(" {error} "))
+                                    .border_style(Style::default().fg(Color::Red));
+                            }
+                            Widget::render(
+                                Paragraph::new(edit.input.as_str()).block(block),
+                                input_area,
+                                buf,
+                            );
+                        }
+                        None => Widget::render(table, right_area, buf),
+                    }
                 }
                 ConfigListItemType::OriginConnections(addr) => {
                     let table = Table::new(
-                        self.config
+                        self.r_config()
                             .origin_connections
                             .get(&addr)
                             .unwrap()
@@ -256,7 +491,7 @@ impl Widget for &ConfigTab {
                 }
                 ConfigListItemType::Labels(chain) => {
                     let table = Table::new(
-                        self.config
+                        self.r_config()
                             .labels
                             .get(&chain)
                             .unwrap()
@@ -278,6 +513,40 @@ impl Widget for &ConfigTab {
                     );
                     Widget::render(table, right_area, buf);
                 }
+                ConfigListItemType::Tokens(chain) => {
+                    let table = Table::new(
+                        self.r_config()
+                            .tokens
+                            .get(&chain)
+                            .unwrap()
+                            .iter()
+                            .map(|(k, v)| {
+                                Row::new(vec![
+                                    k.to_string(),
+                                    v.symbol.clone(),
+                                    v.decimals.to_string(),
+                                ])
+                            })
+                            .collect::<Vec<_>>(),
+                        vec![
+                            Constraint::Length(42),
+                            Constraint::Percentage(50),
+                            Constraint::Percentage(50),
+                        ],
+                    )
+                    .column_spacing(1)
+                    .header(
+                        Row::new(vec!["Address", "Symbol", "Decimals"])
+                            .style(Style::default().bold())
+                            .bottom_margin(1),
+                    )
+                    .block(
+                        Block::bordered()
+                            .title("Tokens")
+                            .padding(Padding::new(1, 0, 0, 0)),
+                    );
+                    Widget::render(table, right_area, buf);
+                }
                 _ => {
                     Widget::render(Block::bordered(), right_area, buf);
                 }
@@ -288,9 +557,29 @@ impl Widget for &ConfigTab {
 
 impl HandleEvent for ConfigTab {
     fn handle_key(&self, event: &KeyEvent) {
+        if self.is_editing() {
+            self.handle_rpc_edit_key(event);
+            return;
+        }
         match event.code {
             KeyCode::Up | KeyCode::Char('k') => self.select_previous_config_type(),
             KeyCode::Down | KeyCode::Char('j') => self.select_next_config_type(),
+            KeyCode::Char('a') | KeyCode::Char('d') => {
+                let selected = self
+                    .config_list_state
+                    .lock()
+                    .expect("failed to get config list state")
+                    .selected();
+                if let Some(idx) = selected
+                    && matches!(self.item_at(idx), ConfigListItemType::Rpcs)
+                {
+                    self.start_rpc_edit(if event.code == KeyCode::Char('a') {
+                        RpcEditKind::Set
+                    } else {
+                        RpcEditKind::Remove
+                    });
+                }
+            }
             KeyCode::Enter => {
                 if let Some(idx) = self
                     .config_list_state
@@ -314,6 +603,13 @@ impl HandleEvent for ConfigTab {
                             };
                             *self.w_labels_collapsed() = new_value;
                         }
+                        ConfigListItemType::TokensMeta => {
+                            let new_value = {
+                                let prev = *self.r_tokens_collapsed();
+                                !prev
+                            };
+                            *self.w_tokens_collapsed() = new_value;
+                        }
                         _ => {}
                     }
                 }
@@ -322,3 +618,16 @@ impl HandleEvent for ConfigTab {
         }
     }
 }
+
+/// Parses the rpcs of a chain typed as `<chain>=<url>[,<url>...]`
+fn parse_chain_rpcs(input: &str) -> eyre::Result<(NamedChain, Vec<Url>)> {
+    let (chain, urls) = input
+        .split_once('=')
+        .ok_or_eyre("expected <chain>=<url>[,<url>...]")?;
+    let chain = chain_id_or_name_to_named_chain(chain.trim())?;
+    let urls = urls
+        .split(',')
+        .map(|url| url.trim().parse())
+        .collect::<Result<Vec<Url>, _>>()?;
+    Ok((chain, urls))
+}
@@ -0,0 +1,253 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use alloy::primitives::Address;
+use alloy_chains::NamedChain;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Layout},
+    prelude::{Buffer, Rect},
+    style::{Color, Style},
+    widgets::{Block, Cell, Paragraph, Row, StatefulWidget, Table, TableState, Widget},
+};
+
+use crate::history::{History, TxRecord, TxStatus, now};
+
+/// Statuses of the transactions listed in the dashboard
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum StatusFilter {
+    #[default]
+    All,
+    Pending,
+    Confirmed,
+    Reverted,
+    Dropped,
+}
+
+impl StatusFilter {
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::Pending,
+            Self::Pending => Self::Confirmed,
+            Self::Confirmed => Self::Reverted,
+            Self::Reverted => Self::Dropped,
+            Self::Dropped => Self::All,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+            Self::Reverted => "reverted",
+            Self::Dropped => "dropped",
+        }
+    }
+
+    fn matches(self, status: &TxStatus) -> bool {
+        matches!(
+            (self, status),
+            (Self::All, _)
+                | (Self::Pending, TxStatus::Pending)
+                | (Self::Confirmed, TxStatus::Confirmed { .. })
+                | (Self::Reverted, TxStatus::Reverted { .. })
+                | (Self::Dropped, TxStatus::Dropped)
+        )
+    }
+}
+
+/// Transaction history of the active account, filtered by status and chain
+pub struct Dashboard {
+    history: Arc<History>,
+    table_state: RwLock<TableState>,
+    status_filter: RwLock<StatusFilter>,
+    /// Chain the transactions are listed for, all of them if `None`
+    chain_filter: RwLock<Option<NamedChain>>,
+}
+
+impl Dashboard {
+    pub fn new(history: Arc<History>) -> Self {
+        let mut table_state = TableState::default();
+        table_state.select_first();
+
+        Self {
+            history,
+            table_state: table_state.into(),
+            status_filter: Default::default(),
+            chain_filter: Default::default(),
+        }
+    }
+
+    pub fn render(&self, account: Option<Address>, block: Block, area: Rect, buf: &mut Buffer) {
+        let status_filter = *self.r_status_filter();
+        let chain_filter = *self.r_chain_filter();
+        let block = block.title_bottom(format!(
+            " [f] Status: {} ───── [c] Chain: {} ",
+            status_filter.name(),
+            chain_filter.map_or("all".to_string(), |chain| chain.to_string())
+        ));
+        let Some(account) = account else {
+            Widget::render(
+                Paragraph::new("Select a wallet to see its transactions").block(block),
+                area,
+                buf,
+            );
+            return;
+        };
+
+        let records = self
+            .history
+            .account_records(account)
+            .into_iter()
+            .filter(|record| status_filter.matches(&record.status))
+            .filter(|record| chain_filter.is_none_or(|chain| chain == record.chain))
+            .collect::<Vec<_>>();
+        let inner = block.inner(area);
+        Widget::render(block, area, buf);
+        if records.is_empty() {
+            Widget::render(Paragraph::new("No transactions"), inner, buf);
+            return;
+        }
+
+        let now = now();
+        let table = Table::new(
+            records.iter().map(|record| {
+                Row::new(vec![
+                    status_cell(&record.status),
+                    Cell::from(record.chain.to_string()),
+                    Cell::from(record.nonce.to_string()),
+                    Cell::from(format_age(now.saturating_sub(record.signed_at))),
+                    Cell::from(record.summary.clone()),
+                ])
+            }),
+            vec![
+                Constraint::Length(18),
+                Constraint::Length(12),
+                Constraint::Length(6),
+                Constraint::Length(5),
+                Constraint::Fill(1),
+            ],
+        )
+        .column_spacing(1)
+        .header(
+            Row::new(vec!["Status", "Chain", "Nonce", "Age", "Summary"])
+                .style(Style::default().bold())
+                .bottom_margin(1),
+        )
+        .row_highlight_style(Style::default().reversed());
+        let [table_area, details_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(2)]).areas(inner);
+        let mut table_state = self.w_table_state();
+        StatefulWidget::render(table, table_area, buf, &mut *table_state);
+
+        if let Some(record) = table_state
+            .selected()
+            .and_then(|idx| records.get(idx.min(records.len() - 1)))
+        {
+            Widget::render(Paragraph::new(details(record)), details_area, buf);
+        }
+    }
+
+    /// Lists the transactions from the first one, after the filters changed
+    fn reset_selection(&self) {
+        self.w_table_state().select_first();
+    }
+
+    /// Lists the transactions of the next chain the account has sent transactions on, then of
+    /// every chain
+    fn select_next_chain(&self, account: Option<Address>) {
+        let chains = account
+            .map(|account| self.history.account_chains(account))
+            .unwrap_or_default();
+        let mut chain_filter = self.w_chain_filter();
+        *chain_filter = match *chain_filter {
+            None => chains.first().copied(),
+            Some(current) => chains
+                .iter()
+                .skip_while(|chain| **chain != current)
+                .nth(1)
+                .copied(),
+        };
+    }
+
+    pub fn handle_key(&self, key: &KeyEvent, account: Option<Address>) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.w_table_state().select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.w_table_state().select_next(),
+            KeyCode::Char('f') => {
+                let next = self.r_status_filter().next();
+                *self.w_status_filter() = next;
+                self.reset_selection();
+            }
+            KeyCode::Char('c') => {
+                self.select_next_chain(account);
+                self.reset_selection();
+            }
+            _ => {}
+        }
+    }
+
+    fn w_table_state(&self) -> RwLockWriteGuard<'_, TableState> {
+        self.table_state
+            .write()
+            .expect("failed to get write lock on table state")
+    }
+
+    fn r_status_filter(&self) -> RwLockReadGuard<'_, StatusFilter> {
+        self.status_filter
+            .read()
+            .expect("failed to get read lock on status filter")
+    }
+
+    fn w_status_filter(&self) -> RwLockWriteGuard<'_, StatusFilter> {
+        self.status_filter
+            .write()
+            .expect("failed to get write lock on status filter")
+    }
+
+    fn r_chain_filter(&self) -> RwLockReadGuard<'_, Option<NamedChain>> {
+        self.chain_filter
+            .read()
+            .expect("failed to get read lock on chain filter")
+    }
+
+    fn w_chain_filter(&self) -> RwLockWriteGuard<'_, Option<NamedChain>> {
+        self.chain_filter
+            .write()
+            .expect("failed to get write lock on chain filter")
+    }
+}
+
+fn status_cell(status: &TxStatus) -> Cell<'static> {
+    let (text, color) = match status {
+        TxStatus::Pending => ("pending".to_string(), Color::Yellow),
+        TxStatus::Confirmed { block } => (format!("confirmed #{block}"), Color::Green),
+        TxStatus::Reverted { block } => (format!("reverted #{block}"), Color::Red),
+        TxStatus::Dropped => ("dropped".to_string(), Color::DarkGray),
+    };
+    Cell::from(text).style(Style::default().fg(color))
+}
+
+/// Hash and origin of the transaction, which don't fit in its row
+fn details(record: &TxRecord) -> String {
+    format!(
+        "Hash:   {}\nOrigin: {}",
+        record.hash,
+        record
+            .origin
+            .as_ref()
+            .map_or("-".to_string(), |origin| origin
+                .origin()
+                .ascii_serialization())
+    )
+}
+
+/// Formats the age in the largest unit it has, e.g. `5m` or `2d`
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
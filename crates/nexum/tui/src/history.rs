@@ -0,0 +1,329 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    consensus::{EthereumTypedTransaction, Transaction, TxEip4844Variant},
+    primitives::{Address, TxHash, TxKind},
+    providers::{Provider, RootProvider},
+    sol,
+    sol_types::SolCall,
+    transports::TransportResult,
+};
+use alloy_chains::NamedChain;
+use nexum_rpc::{
+    rpc::Chains,
+    simulation::{Asset, AssetChange, Simulation},
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    config::{Config, config_dir},
+    describe_asset_change,
+};
+
+/// How often the receipts of the pending transactions are polled
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+/// How long a transaction the upstream doesn't know of stays pending before it's considered
+/// dropped, e.g. a transaction signed with `eth_signTransaction` that was never broadcast
+const DROPPED_AFTER: Duration = Duration::from_secs(600);
+
+/// Number of transactions kept in the history, the oldest ones are forgotten first
+const MAX_RECORDS: usize = 1000;
+
+sol! {
+    function transfer(address to, uint256 amount);
+    function approve(address spender, uint256 amount);
+}
+
+/// Transaction signed by the wallet, as kept in the history
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxRecord {
+    pub chain: NamedChain,
+    /// Origin the transaction was signed for, `None` for clients that aren't web pages
+    pub origin: Option<Url>,
+    pub from: Address,
+    pub hash: TxHash,
+    pub nonce: u64,
+    /// What the transaction does, as shown to the user
+    pub summary: String,
+    pub status: TxStatus,
+    /// When the transaction was signed, in seconds since the Unix epoch
+    pub signed_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    Pending,
+    /// Included in the block and succeeded
+    Confirmed {
+        block: u64,
+    },
+    /// Included in the block and reverted
+    Reverted {
+        block: u64,
+    },
+    /// The upstream lost track of the transaction, or another transaction took its nonce
+    Dropped,
+}
+
+impl TxStatus {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending)
+    }
+}
+
+/// The last [`MAX_RECORDS`] transactions signed by the wallet, oldest first, persisted to
+/// `history.json` in the config directory
+#[derive(Debug, Default)]
+pub struct History {
+    records: RwLock<Vec<TxRecord>>,
+}
+
+impl History {
+    /// Reads the history, it starts empty if it's missing. An unreadable history is moved aside
+    /// to `history.json.bak`, so it isn't overwritten by the next transaction.
+    pub fn load() -> Self {
+        let path = match history_path() {
+            Ok(path) => path,
+            Err(err) => {
+                tracing::error!(?err, "failed to locate the history, starting an empty one");
+                return Self::default();
+            }
+        };
+        let history = match std::fs::read_to_string(&path) {
+            Ok(history) => history,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                tracing::error!(?err, "failed to read the history, starting an empty one");
+                move_aside(&path);
+                return Self::default();
+            }
+        };
+        match serde_json::from_str(&history) {
+            Ok(records) => Self {
+                records: RwLock::new(records),
+            },
+            Err(err) => {
+                tracing::error!(?err, "failed to parse the history, starting an empty one");
+                move_aside(&path);
+                Self::default()
+            }
+        }
+    }
+
+    /// Adds the transaction to the history and persists it
+    pub fn record(&self, record: TxRecord) {
+        let mut records = self.w_records();
+        records.push(record);
+        let forgotten = records.len().saturating_sub(MAX_RECORDS);
+        records.drain(..forgotten);
+        save_history(&records)
+            .inspect_err(|err| tracing::error!(?err, "failed to save history"))
+            .ok();
+    }
+
+    /// Transactions of the account, newest first
+    pub fn account_records(&self, account: Address) -> Vec<TxRecord> {
+        self.r_records()
+            .iter()
+            .rev()
+            .filter(|record| record.from == account)
+            .cloned()
+            .collect()
+    }
+
+    /// Chains the account has sent transactions on
+    pub fn account_chains(&self, account: Address) -> Vec<NamedChain> {
+        let mut chains = self
+            .r_records()
+            .iter()
+            .filter(|record| record.from == account)
+            .map(|record| record.chain)
+            .collect::<Vec<_>>();
+        chains.sort_by_key(|chain| *chain as u64);
+        chains.dedup();
+        chains
+    }
+
+    fn pending(&self) -> Vec<TxRecord> {
+        self.r_records()
+            .iter()
+            .filter(|record| record.status.is_pending())
+            .cloned()
+            .collect()
+    }
+
+    fn set_status(&self, chain: NamedChain, hash: TxHash, status: TxStatus) {
+        let mut records = self.w_records();
+        let Some(record) = records
+            .iter_mut()
+            .find(|record| record.chain == chain && record.hash == hash)
+        else {
+            return;
+        };
+        record.status = status;
+        save_history(&records)
+            .inspect_err(|err| tracing::error!(?err, "failed to save history"))
+            .ok();
+    }
+
+    fn r_records(&self) -> RwLockReadGuard<'_, Vec<TxRecord>> {
+        self.records
+            .read()
+            .expect("failed to get read lock on history")
+    }
+
+    fn w_records(&self) -> RwLockWriteGuard<'_, Vec<TxRecord>> {
+        self.records
+            .write()
+            .expect("failed to get write lock on history")
+    }
+}
+
+/// Returns the path of the history file, `history.json` in the config directory
+fn history_path() -> eyre::Result<PathBuf> {
+    Ok(config_dir()?.join("history.json"))
+}
+
+/// Renames the unreadable history to `history.json.bak`, replacing the previous backup
+fn move_aside(path: &Path) {
+    let backup = path.with_extension("json.bak");
+    match std::fs::rename(path, &backup) {
+        Ok(()) => tracing::warn!(backup = %backup.display(), "moved the unreadable history aside"),
+        Err(err) => tracing::error!(?err, "failed to move the unreadable history aside"),
+    }
+}
+
+fn save_history(records: &[TxRecord]) -> eyre::Result<()> {
+    std::fs::write(history_path()?, serde_json::to_string_pretty(records)?)?;
+    Ok(())
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Describes what the transaction does: the changes to the assets of the sender its simulation
+/// found, or else its calldata decoded as a transfer or an approval of ERC-20 tokens
+pub fn summarize(
+    tx: &EthereumTypedTransaction<TxEip4844Variant>,
+    simulation: Option<&Simulation>,
+    chain: Option<NamedChain>,
+    config: &Config,
+) -> String {
+    let changes = match simulation {
+        Some(Simulation::Success {
+            changes: Some(changes),
+            ..
+        }) if !changes.is_empty() => changes.clone(),
+        _ => decode_changes(tx),
+    };
+    if !changes.is_empty() {
+        return changes
+            .iter()
+            .map(|change| describe_asset_change(change, chain, config))
+            .collect::<Vec<_>>()
+            .join(", ");
+    }
+    match (tx.kind(), tx.input().get(..4)) {
+        (TxKind::Create, _) => "deploy a contract".to_string(),
+        (TxKind::Call(to), Some(selector)) => {
+            format!("call 0x{} on {to}", alloy::hex::encode(selector))
+        }
+        (TxKind::Call(to), None) => format!("call {to}"),
+    }
+}
+
+/// Changes the transaction makes to the assets of its sender, as read from its value and calldata
+fn decode_changes(tx: &EthereumTypedTransaction<TxEip4844Variant>) -> Vec<AssetChange> {
+    let TxKind::Call(to) = tx.kind() else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    if !tx.value().is_zero() {
+        changes.push(AssetChange::Send {
+            asset: Asset::Native(tx.value()),
+            to,
+        });
+    }
+    if let Ok(call) = transferCall::abi_decode(tx.input()) {
+        changes.push(AssetChange::Send {
+            asset: Asset::Erc20 {
+                token: to,
+                amount: call.amount,
+            },
+            to: call.to,
+        });
+    } else if let Ok(call) = approveCall::abi_decode(tx.input()) {
+        changes.push(AssetChange::Approve {
+            asset: Asset::Erc20 {
+                token: to,
+                amount: call.amount,
+            },
+            spender: call.spender,
+        });
+    }
+    changes
+}
+
+/// Polls the receipts of the pending transactions of the history until they're confirmed or
+/// dropped. Transactions on chains that aren't served stay pending until they are.
+pub async fn track_receipts(history: Arc<History>, chains: Chains) {
+    let mut interval = tokio::time::interval(RECEIPT_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        for record in history.pending() {
+            let Some(provider) = chains.provider(record.chain) else {
+                continue;
+            };
+            match poll_status(&provider, &record).await {
+                Ok(TxStatus::Pending) => {}
+                Ok(status) => {
+                    tracing::info!(chain = %record.chain, hash = %record.hash, ?status, "transaction settled");
+                    history.set_status(record.chain, record.hash, status);
+                }
+                Err(err) => {
+                    tracing::debug!(?err, chain = %record.chain, hash = %record.hash, "failed to poll receipt")
+                }
+            }
+        }
+    }
+}
+
+async fn poll_status(provider: &RootProvider, record: &TxRecord) -> TransportResult<TxStatus> {
+    // the count is read before the receipt, so a transaction included in between isn't taken
+    // for dropped
+    let count = provider.get_transaction_count(record.from).latest().await?;
+    if let Some(receipt) = provider.get_transaction_receipt(record.hash).await? {
+        let block = receipt.block_number.unwrap_or_default();
+        return Ok(if receipt.status() {
+            TxStatus::Confirmed { block }
+        } else {
+            TxStatus::Reverted { block }
+        });
+    }
+    // another transaction took the nonce, e.g. the transaction was replaced
+    if count > record.nonce {
+        return Ok(TxStatus::Dropped);
+    }
+    let expired = now().saturating_sub(record.signed_at) > DROPPED_AFTER.as_secs();
+    if expired
+        && provider
+            .get_transaction_by_hash(record.hash)
+            .await?
+            .is_none()
+    {
+        return Ok(TxStatus::Dropped);
+    }
+    Ok(TxStatus::Pending)
+}
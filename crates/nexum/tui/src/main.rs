@@ -1,45 +1,62 @@
 use std::{
+    collections::HashMap,
     fs::OpenOptions,
-    net::Ipv4Addr,
+    net::IpAddr,
+    path::PathBuf,
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
     time::Duration,
 };
 
 use alloy::{
-    consensus::{EthereumTypedTransaction, SignableTransaction, TxEip4844Variant},
+    consensus::{EthereumTypedTransaction, SignableTransaction, Transaction, TxEip4844Variant},
     dyn_abi::TypedData,
-    primitives::{Address, B256, Bytes},
+    primitives::{Address, B256, Bytes, U256, utils::format_units},
+    rpc::types::TransactionRequest,
     signers::Signature,
 };
 use alloy_chains::NamedChain;
 use clap::Parser;
 use config_tab::ConfigTab;
 use crossterm::event::{Event, EventStream, KeyCode, KeyEvent};
+use dashboard::Dashboard;
 use futures::StreamExt;
+use history::{History, TxRecord, TxStatus, now, summarize, track_receipts};
+use nexum_rpc::error::WalletError;
+use nexum_rpc::fees::{FeeStrategies, FeeStrategy};
 use nexum_rpc::rpc::{
-    InteractiveRequest, InteractiveResponse, RpcServerBuilder, chain_id_or_name_to_named_chain,
+    BatchCall, Chains, InteractiveRequest, InteractiveResponse, LegacyTypedData, RpcServerBuilder,
+    TokenMetadata, chain_id_or_name_to_named_chain,
 };
+use nexum_rpc::simulation::{Asset, AssetChange, Simulation};
+use nexum_rpc::status::Status;
 use ratatui::{
     DefaultTerminal, Frame,
     layout::{Constraint, HorizontalAlignment, Layout},
     prelude::{Buffer, Rect},
     style::{Color, Style},
     symbols,
-    text::Text,
+    text::{Line, Text},
     widgets::{
         Block, Borders, FrameExt, List, ListState, Padding, Paragraph, StatefulWidget, Tabs, Widget,
     },
 };
 use signers::{NexumAccount, NexumSigner, load_ledger_accounts};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tracing_subscriber::EnvFilter;
+use tx_edit::{TxEdit, fee_lines};
 
-use config::{Config, config_dir, load_config};
+use config::{Config, config_dir, config_path, load_config, read_config};
 use url::Url;
 
 mod config;
 mod config_tab;
+mod dashboard;
+mod history;
 mod signers;
+mod tx_edit;
+
+/// How often `nxm.toml` is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 fn tui_logger() -> impl std::io::Write {
     let log_file = config_dir()
@@ -54,12 +71,19 @@ fn tui_logger() -> impl std::io::Write {
 
 #[derive(Parser)]
 struct Args {
-    #[arg(short = 'H', long, default_value = "127.0.0.1")]
-    host: Ipv4Addr,
+    /// Addresses to listen on, repeat to listen on several, e.g. `-H 127.0.0.1 -H ::1`
+    #[arg(short = 'H', long = "host", default_value = "127.0.0.1")]
+    hosts: Vec<IpAddr>,
     #[arg(short, long, default_value = "1248")]
     port: u16,
     #[arg(short, long)]
     rpc_urls: Vec<String>,
+    /// Also listen for raw JSON-RPC on a Unix domain socket at this path, overrides the config
+    #[arg(long)]
+    ipc_path: Option<PathBuf>,
+    /// Also serve JSON-RPC over WebTransport on this UDP port, overrides the config
+    #[arg(long)]
+    webtransport_port: Option<u16>,
     /// Prank mode: report this address for eth_requestAccounts/eth_accounts
     /// without having the private key. Signing operations will show prompts but fail.
     #[arg(long, value_name = "ADDRESS")]
@@ -94,24 +118,36 @@ async fn main() -> eyre::Result<()> {
         accounts
     };
 
-    let mut builder = RpcServerBuilder::new().host(args.host).port(args.port);
-    let mut rpcs = config.chain_rpcs();
-    rpcs.extend(
-        args.rpc_urls
-            .iter()
-            .map(|s: &String| -> eyre::Result<(NamedChain, Url)> {
-                let (chain, rpc) = s
-                    .split_once("=")
-                    .ok_or_else(|| eyre::eyre!("invalid format for rpc url"))?;
-                let chain = chain_id_or_name_to_named_chain(chain)?;
-                Ok((chain, rpc.parse()?))
-            })
-            .collect::<eyre::Result<Vec<_>>>()?,
-    );
-    // since the cli rpcs are added after the config rpcs, the cli rpcs will override
-    // the config rpcs if the same chain is specified
-    for (chain, url) in rpcs {
-        builder = builder.chain(chain, url);
+    let mut builder = RpcServerBuilder::new()
+        .hosts(args.hosts)
+        .port(args.port)
+        .allowed_hosts(config.server.allowed_hosts.clone())
+        .allowed_origins(config.server.allowed_origins.clone());
+    if let Some(token) = &config.server.token {
+        builder = builder.token(token.clone());
+    }
+    if let Some(ipc_path) = args.ipc_path.or_else(|| config.server.ipc_path.clone()) {
+        builder = builder.ipc_path(ipc_path);
+    }
+    if let Some(port) = args.webtransport_port.or(config.server.webtransport_port) {
+        builder = builder.webtransport_port(port);
+    }
+    let cli_rpcs = args
+        .rpc_urls
+        .iter()
+        .map(|s: &String| -> eyre::Result<(NamedChain, Url)> {
+            let (chain, rpc) = s
+                .split_once("=")
+                .ok_or_else(|| eyre::eyre!("invalid format for rpc url"))?;
+            let chain = chain_id_or_name_to_named_chain(chain)?;
+            Ok((chain, rpc.parse()?))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    for (chain, urls) in server_rpcs(config.chain_rpcs(), &cli_rpcs) {
+        builder = builder.upstreams(chain, urls);
+    }
+    for (chain, strategy) in config.fee_strategies() {
+        builder = builder.fee_strategy(chain, strategy);
     }
 
     let mut rpc = builder.build().await;
@@ -119,7 +155,26 @@ async fn main() -> eyre::Result<()> {
 
     let terminal = ratatui::init();
 
-    let app = App::new(req_receiver, config, initial_accounts).await;
+    let history = Arc::new(History::load());
+    let app = App::new(
+        req_receiver,
+        config,
+        initial_accounts,
+        rpc.status().clone(),
+        history.clone(),
+    )
+    .await;
+    tokio::spawn(sync_chains(
+        rpc.chains().clone(),
+        app.config_tab.subscribe_rpcs(),
+        cli_rpcs,
+    ));
+    tokio::spawn(sync_fee_strategies(
+        rpc.chains().fee_strategies().clone(),
+        app.config_tab.subscribe_fee_strategies(),
+    ));
+    tokio::spawn(watch_config(app.config_tab.clone()));
+    tokio::spawn(track_receipts(history, rpc.chains().clone()));
     // run the loop until the tui quits or the server quits
     let app_result = tokio::select! {
         app_result = app.run(terminal) => { app_result }
@@ -129,6 +184,76 @@ async fn main() -> eyre::Result<()> {
     app_result
 }
 
+/// Upstream rpcs of each chain, the rpcs given on the command line override the config rpcs of
+/// their chain
+fn server_rpcs(
+    config_rpcs: Vec<(NamedChain, Vec<Url>)>,
+    cli_rpcs: &[(NamedChain, Url)],
+) -> HashMap<NamedChain, Vec<Url>> {
+    let mut rpcs = config_rpcs
+        .into_iter()
+        .filter(|(chain, _)| !cli_rpcs.iter().any(|(cli_chain, _)| cli_chain == chain))
+        .collect::<HashMap<_, _>>();
+    for (chain, url) in cli_rpcs {
+        rpcs.entry(*chain).or_default().push(url.clone());
+    }
+    rpcs
+}
+
+/// Serves the chains of the config as it changes, without restarting the server
+async fn sync_chains(
+    chains: Chains,
+    mut config_rpcs: watch::Receiver<Vec<(NamedChain, Vec<Url>)>>,
+    cli_rpcs: Vec<(NamedChain, Url)>,
+) {
+    while config_rpcs.changed().await.is_ok() {
+        let rpcs = server_rpcs(config_rpcs.borrow_and_update().clone(), &cli_rpcs);
+        tracing::info!(chains = ?rpcs.keys().collect::<Vec<_>>(), "updating served chains");
+        chains.sync(rpcs).await;
+    }
+}
+
+/// Prices the transactions with the fee strategies of the config as they change
+async fn sync_fee_strategies(
+    fee_strategies: FeeStrategies,
+    mut config_fee_strategies: watch::Receiver<HashMap<NamedChain, FeeStrategy>>,
+) {
+    while config_fee_strategies.changed().await.is_ok() {
+        let strategies = config_fee_strategies.borrow_and_update().clone();
+        tracing::info!(?strategies, "updating fee strategies");
+        fee_strategies.sync(strategies);
+    }
+}
+
+/// Reloads the config when `nxm.toml` is changed by something else than the tui, an invalid
+/// file is ignored until it's fixed
+async fn watch_config(config_tab: Arc<ConfigTab>) {
+    let Ok(path) = config_path() else {
+        return;
+    };
+    let modified = || {
+        std::fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    let mut last_modified = modified();
+    let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let modified = modified();
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+        match read_config() {
+            Ok(config) => config_tab.reload(config),
+            Err(err) => {
+                tracing::warn!(?err, "failed to reload the config, keeping the current one")
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 enum AppPane {
     Tabs,
@@ -192,10 +317,15 @@ struct App {
     wallet_pane: Arc<WalletPane>,
     prompt: Option<Prompt>,
     prompt_input: String,
+    /// Edit of the gas, fees and nonce of the transaction of the prompt, if the user is editing
+    tx_edit: Option<TxEdit>,
     prompt_receiver: mpsc::UnboundedReceiver<Prompt>,
     prompt_sender: mpsc::UnboundedSender<Prompt>,
     request_receiver: mpsc::Receiver<(InteractiveRequest, oneshot::Sender<InteractiveResponse>)>,
-    config_tab: ConfigTab,
+    config_tab: Arc<ConfigTab>,
+    /// Transactions signed by the wallet, each is recorded once signed
+    history: Arc<History>,
+    dashboard: Dashboard,
 }
 
 impl App {
@@ -208,6 +338,8 @@ impl App {
         )>,
         config: Config,
         initial_accounts: Vec<NexumAccount>,
+        status: Status,
+        history: Arc<History>,
     ) -> Self {
         let mut list_state = ListState::default();
         list_state.select_first();
@@ -224,14 +356,19 @@ impl App {
                 accounts: RwLock::new(initial_accounts),
                 list_state: RwLock::new(list_state),
                 active_wallet_idx: RwLock::new(None),
+                unlocking: RwLock::new(None),
                 prompt_sender: sender.clone(),
+                status,
             }),
             prompt: None,
             prompt_input: "".to_string(),
+            tx_edit: None,
             prompt_sender: sender.clone(),
             prompt_receiver: receiver,
             request_receiver,
-            config_tab: ConfigTab::new(config),
+            config_tab: Arc::new(ConfigTab::new(config)),
+            dashboard: Dashboard::new(history.clone()),
+            history,
         }
     }
 
@@ -242,8 +379,9 @@ impl App {
 
         // load ledger accounts in background because its too slow
         let wallet_pane_clone = self.wallet_pane.clone();
+        let n_ledger_accounts = self.config_tab.r_config().signer.ledger.n;
         tokio::spawn(async move {
-            load_ledger_accounts(self.config_tab.config.signer.ledger.n)
+            load_ledger_accounts(n_ledger_accounts)
                 .await
                 .map(|accounts| wallet_pane_clone.add_accounts(accounts))
                 .ok();
@@ -327,13 +465,18 @@ impl App {
                         AppPane::Dashboard => active_border_style,
                         AppPane::Tabs => inactive_border_style,
                     });
-                frame.render_widget(dashboard_block, right_area);
+                self.dashboard.render(
+                    self.wallet_pane.active_account(),
+                    dashboard_block,
+                    right_area,
+                    frame.buffer_mut(),
+                );
 
                 // render the popup password prompt
                 self.render_prompt(frame);
             }
             AppTab::Settings => {
-                frame.render_widget_ref(&self.config_tab, tab_inner);
+                frame.render_widget_ref(&*self.config_tab, tab_inner);
             }
         }
 
@@ -341,105 +484,182 @@ impl App {
     }
 
     fn render_prompt(&mut self, frame: &mut Frame) {
-        if let Some(prompt) = &self.prompt {
-            match prompt {
-                Prompt::AccountUnlock(name) => {
-                    let masked_pwd = "*".repeat(self.prompt_input.len());
-                    let prompt_str = format!(" Enter password for {name} ");
-                    let prompt_area = frame.area().centered(
-                        Constraint::Length(
-                            prompt_str
-                                .len()
-                                .max(52)
-                                .try_into()
-                                .expect("cannot convert to u16"),
-                        ),
-                        Constraint::Length(3),
-                    );
-                    let paragraph = Paragraph::new(masked_pwd).block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title(prompt_str)
-                            .border_style(Style::default().fg(Color::Blue)),
-                    );
-                    frame.render_widget(paragraph, prompt_area);
-                }
-                Prompt::AccountUnlockInvalidPasswordRetry(name) => {
-                    let masked_pwd = "*".repeat(self.prompt_input.len());
-                    let prompt_str = format!(" Incorrect password for {name}! Try again. ");
-                    let prompt_area = frame.area().centered(
-                        Constraint::Length(
-                            prompt_str
-                                .len()
-                                .max(52)
-                                .try_into()
-                                .expect("cannot convert to u16"),
-                        ),
-                        Constraint::Length(3),
-                    );
-                    let paragraph = Paragraph::new(masked_pwd).block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title(prompt_str)
-                            .border_style(Style::default().fg(Color::Blue)),
-                    );
-                    frame.render_widget(paragraph, prompt_area);
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        let (title, text) = match prompt {
+            Prompt::AccountUnlock(name) | Prompt::AccountUnlockInvalidPasswordRetry(name) => {
+                let prompt_str = if matches!(prompt, Prompt::AccountUnlock(_)) {
+                    format!(" Enter password for {name} ")
+                } else {
+                    format!(" Incorrect password for {name}! Try again. ")
+                };
+                let masked_pwd = "*".repeat(self.prompt_input.len());
+                let prompt_area = frame.area().centered(
+                    Constraint::Length(
+                        prompt_str
+                            .len()
+                            .max(52)
+                            .try_into()
+                            .expect("cannot convert to u16"),
+                    ),
+                    Constraint::Length(3),
+                );
+                let paragraph = Paragraph::new(masked_pwd).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(prompt_str)
+                        .border_style(Style::default().fg(Color::Blue)),
+                );
+                frame.render_widget(paragraph, prompt_area);
+                return;
+            }
+            Prompt::SendTransaction(from, req, simulation, _) => (
+                " Send Transaction ",
+                self.transaction_text(*from, req, simulation.as_ref()),
+            ),
+            Prompt::EthSign(_, message, _) => {
+                (" Sign EIP-191 Message ", Text::from(message.to_string()))
+            }
+            Prompt::ConnectOrigin(origin, account, _) => (
+                " Connect ",
+                Text::from(format!(
+                    "{} wants to access\n{account}",
+                    origin.origin().ascii_serialization()
+                )),
+            ),
+            Prompt::AddChain(chain, rpc_urls, _) => {
+                let rpcs = rpc_urls
+                    .iter()
+                    .map(Url::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n       ");
+                (
+                    " Add Chain ",
+                    Text::from(format!("Chain: {chain} ({})\nRPCs:  {rpcs}", *chain as u64)),
+                )
+            }
+            Prompt::WatchAsset(chain, address, metadata, _) => (
+                " Watch Asset ",
+                Text::from(format!(
+                    "Chain:    {chain}\nToken:    {address}\nSymbol:   {}\nDecimals: {}",
+                    metadata.symbol, metadata.decimals
+                )),
+            ),
+            Prompt::EthSignLegacyTypedData(_, data, _) => (
+                " Sign Typed Data (legacy) ",
+                Text::from(
+                    data.0
+                        .iter()
+                        .map(|field| format!("{} {}: {}", field.type_, field.name, field.value))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            ),
+            Prompt::SendCalls(chain, from, calls, _) => {
+                let mut text = format!("Chain: {chain}\nFrom:  {from}\n");
+                for (i, call) in calls.iter().enumerate() {
+                    text.push_str(&format!(
+                        "\n#{i} to: {} value: {}\n   data: {}",
+                        call.to
+                            .and_then(|to| to.to().copied())
+                            .map_or("contract creation".to_string(), |to| to.to_string()),
+                        call.value.unwrap_or_default(),
+                        call.input.input().cloned().unwrap_or_default(),
+                    ));
                 }
-                Prompt::SendTransaction(req, _) => {
-                    let block = Block::bordered()
-                        .padding(Padding::uniform(1))
-                        .title(" Send Transaction ")
-                        .title_alignment(HorizontalAlignment::Center)
-                        .title_bottom("[A]ccept ───── [R]eject");
-                    let text = match req.as_ref() {
-                        EthereumTypedTransaction::Legacy(tx_legacy) => {
-                            format!("{tx_legacy:#?}")
-                        }
-                        EthereumTypedTransaction::Eip2930(tx_eip2930) => format!("{tx_eip2930:#?}"),
-                        EthereumTypedTransaction::Eip1559(tx_eip1559) => format!("{tx_eip1559:#?}"),
-                        EthereumTypedTransaction::Eip4844(tx_eip4844) => format!("{tx_eip4844:#?}"),
-                        EthereumTypedTransaction::Eip7702(tx_eip7702) => format!("{tx_eip7702:#?}"),
+                (" Send Calls ", Text::from(text))
+            }
+            Prompt::Batch(origin, calls, _) => {
+                let mut text = Text::from(format!(
+                    "Origin: {}",
+                    origin.as_ref().map_or("-".to_string(), |origin| origin
+                        .origin()
+                        .ascii_serialization())
+                ));
+                let config = self.config_tab.r_config();
+                for (i, call) in calls.iter().enumerate() {
+                    text.push_line(Line::default());
+                    let Some(transaction) = &call.transaction else {
+                        text.push_line(format!("#{i} {}", call.method));
+                        text.push_line(format!("   {}", call.params));
+                        continue;
                     };
-
-                    let n_lines = text.lines().count();
-                    let para = Paragraph::new(text).block(block);
-                    let prompt_area = frame.area().centered(
-                        Constraint::Length(80),
-                        Constraint::Length(n_lines as u16 + 4),
-                    );
-                    frame.render_widget(para, prompt_area);
-                }
-                Prompt::EthSign(_, message, _) => {
-                    let block = Block::bordered()
-                        .padding(Padding::uniform(1))
-                        .title(" Sign EIP-191 Message ")
-                        .title_alignment(HorizontalAlignment::Center)
-                        .title_bottom("[A]ccept ───── [R]eject");
-                    frame.render_widget(
-                        Paragraph::new(message.to_string()).block(block),
-                        frame
-                            .area()
-                            .centered(Constraint::Length(80), Constraint::Length(10 + 4)),
-                    );
-                }
-                Prompt::EthSignTypedData(_, data, _) => {
-                    let block = Block::bordered()
-                        .padding(Padding::uniform(1))
-                        .title(" Sign Typed Data ")
-                        .title_alignment(HorizontalAlignment::Center)
-                        .title_bottom("[A]ccept ───── [R]eject");
-                    let text = format!("{data:#?}");
-                    let n_lines = text.lines().count();
-                    frame.render_widget(
-                        Paragraph::new(text).block(block),
-                        frame.area().centered(
-                            Constraint::Length(80),
-                            Constraint::Length((n_lines as u16) + 4),
-                        ),
-                    );
+                    // transactions are summarized, with the fees and nonce they're signed with
+                    let tx = &transaction.tx;
+                    let chain = tx.chain_id().and_then(|id| NamedChain::try_from(id).ok());
+                    text.push_line(format!(
+                        "#{i} {} from {}: {}",
+                        call.method,
+                        transaction.from,
+                        summarize(tx, Some(&transaction.simulation), chain, &config)
+                    ));
+                    text.push_line(simulation_line(&transaction.simulation));
+                    text.extend(fee_lines(tx, None, chain));
                 }
+                (" Batch ", text)
+            }
+            Prompt::EthSignTypedData(_, data, _) => {
+                (" Sign Typed Data ", Text::from(format!("{data:#?}")))
+            }
+        };
+        let keys = match prompt {
+            Prompt::SendTransaction(..) if self.tx_edit.is_some() => {
+                "[Tab] Next field ───── [Enter] Apply ───── [Esc] Cancel"
+            }
+            Prompt::SendTransaction(..) => "[A]ccept ───── [E]dit ───── [R]eject",
+            Prompt::Batch(..) => "[A]ccept all ───── [R]eject all",
+            _ => "[A]ccept ───── [R]eject",
+        };
+        let block = Block::bordered()
+            .padding(Padding::uniform(1))
+            .title(title)
+            .title_alignment(HorizontalAlignment::Center)
+            .title_bottom(keys);
+        let prompt_area = frame.area().centered(
+            Constraint::Length(80),
+            Constraint::Length(text.height() as u16 + 4),
+        );
+        frame.render_widget(Paragraph::new(text).block(block), prompt_area);
+    }
+
+    /// The transaction to sign, below the outcome of its simulation and its fees
+    fn transaction_text(
+        &self,
+        from: Address,
+        req: &EthereumTypedTransaction<TxEip4844Variant>,
+        simulation: Option<&Simulation>,
+    ) -> Text<'static> {
+        let text = match req {
+            EthereumTypedTransaction::Legacy(tx_legacy) => format!("{tx_legacy:#?}"),
+            EthereumTypedTransaction::Eip2930(tx_eip2930) => format!("{tx_eip2930:#?}"),
+            EthereumTypedTransaction::Eip1559(tx_eip1559) => format!("{tx_eip1559:#?}"),
+            EthereumTypedTransaction::Eip4844(tx_eip4844) => format!("{tx_eip4844:#?}"),
+            EthereumTypedTransaction::Eip7702(tx_eip7702) => format!("{tx_eip7702:#?}"),
+        };
+        let mut text = Text::from(format!("From: {from}\n{text}"));
+        let chain = req.chain_id().and_then(|id| NamedChain::try_from(id).ok());
+        let mut preview = Vec::new();
+        if let Some(simulation) = simulation {
+            preview.push(simulation_line(simulation));
+            if let Simulation::Success {
+                changes: Some(changes),
+                ..
+            } = simulation
+            {
+                let config = self.config_tab.r_config();
+                preview.extend(
+                    changes
+                        .iter()
+                        .map(|change| Line::from(describe_asset_change(change, chain, &config))),
+                );
             }
+            preview.push(Line::default());
         }
+        preview.extend(fee_lines(req, self.tx_edit.as_ref(), chain));
+        preview.push(Line::default());
+        text.lines.splice(0..0, preview);
+        text
     }
 
     fn handle_event(&mut self, event: &Event) {
@@ -455,6 +675,7 @@ impl App {
                             KeyCode::Esc => {
                                 self.prompt = None;
                                 self.prompt_input.clear();
+                                self.wallet_pane.on_prompt_cancel();
                             }
                             KeyCode::Enter => {
                                 self.prompt = None;
@@ -465,67 +686,51 @@ impl App {
                             _ => {}
                         }
                     }
-                    Prompt::SendTransaction(_, _) => match key.code {
-                        KeyCode::Esc | KeyCode::Char('r') | KeyCode::Char('R') => {
-                            if let Some(Prompt::SendTransaction(tx, sender)) = self.prompt.take() {
-                                sender
-                                    .send((tx, false))
-                                    .expect("failed to send send transaction prompt response");
-                            }
-                        }
-                        KeyCode::Char('a') | KeyCode::Char('A') => {
-                            if let Some(Prompt::SendTransaction(tx, sender)) = self.prompt.take() {
-                                sender
-                                    .send((tx, true))
-                                    .expect("failed to send send transaction prompt response");
-                            }
-                        }
-                        _ => {}
-                    },
-                    Prompt::EthSign(_, _, _) => match key.code {
-                        KeyCode::Esc | KeyCode::Char('r') | KeyCode::Char('R') => {
-                            if let Some(Prompt::EthSign(signer_addr, message, sender)) =
-                                self.prompt.take()
-                            {
-                                sender
-                                    .send((signer_addr, message, false))
-                                    .expect("failed to send eth_sign prompt response");
-                            }
-                        }
-                        KeyCode::Char('a') | KeyCode::Char('A') => {
-                            if let Some(Prompt::EthSign(signer_addr, message, sender)) =
-                                self.prompt.take()
-                            {
-                                sender
-                                    .send((signer_addr, message, true))
-                                    .expect("failed to send eth_sign prompt response");
+                    // the fields being edited take all the keys
+                    Prompt::SendTransaction(..) if self.tx_edit.is_some() => {
+                        let edit = self.tx_edit.as_mut().expect("transaction is being edited");
+                        match key.code {
+                            KeyCode::Char(ch) => edit.push(ch),
+                            KeyCode::Backspace => edit.pop(),
+                            KeyCode::Tab | KeyCode::Down => edit.select(true),
+                            KeyCode::BackTab | KeyCode::Up => edit.select(false),
+                            KeyCode::Esc => self.tx_edit = None,
+                            KeyCode::Enter => {
+                                if let Some(fees) = edit.finish()
+                                    && let Some(Prompt::SendTransaction(_, tx, _, _)) =
+                                        &mut self.prompt
+                                {
+                                    fees.apply(tx);
+                                    self.tx_edit = None;
+                                }
                             }
+                            _ => {}
                         }
-                        _ => {}
-                    },
-                    Prompt::EthSignTypedData(_, _, _) => match key.code {
+                    }
+                    Prompt::SendTransaction(_, tx, _, _)
+                        if matches!(key.code, KeyCode::Char('e') | KeyCode::Char('E')) =>
+                    {
+                        self.tx_edit = Some(TxEdit::new(tx));
+                    }
+                    _ => match key.code {
                         KeyCode::Esc | KeyCode::Char('r') | KeyCode::Char('R') => {
-                            if let Some(Prompt::EthSignTypedData(signer_addr, data, sender)) =
-                                self.prompt.take()
-                            {
-                                sender
-                                    .send((signer_addr, data, false))
-                                    .expect("failed to send eth_sign_typed_data prompt response");
+                            if let Some(prompt) = self.prompt.take() {
+                                prompt.answer(false);
                             }
                         }
                         KeyCode::Char('a') | KeyCode::Char('A') => {
-                            if let Some(Prompt::EthSignTypedData(signer_addr, data, sender)) =
-                                self.prompt.take()
-                            {
-                                sender
-                                    .send((signer_addr, data, true))
-                                    .expect("failed to send eth_sign_typed_data prompt response");
+                            if let Some(prompt) = self.prompt.take() {
+                                prompt.answer(true);
                             }
                         }
                         _ => {}
                     },
                 },
                 None => match (&self.active_tab, key.code) {
+                    // the settings being typed in take all the keys
+                    (AppTab::Settings, _) if self.config_tab.is_editing() => {
+                        self.config_tab.handle_key(&key);
+                    }
                     // global keybinds
                     (_, KeyCode::Char('q') | KeyCode::Esc) => self.should_quit = true,
                     (_, KeyCode::Char('1')) => self.active_tab = AppTab::id_to_tab(1).unwrap(),
@@ -538,6 +743,9 @@ impl App {
                                 .set_is_active(matches!(self.active_app_pane, AppPane::Wallet));
                         }
                         (AppPane::Wallet, _) => self.wallet_pane.handle_key(&key),
+                        (AppPane::Dashboard, _) => self
+                            .dashboard
+                            .handle_key(&key, self.wallet_pane.active_account()),
                         (AppPane::Tabs, KeyCode::Right | KeyCode::Char('l')) => {
                             self.active_tab = self.active_tab.next();
                         }
@@ -556,77 +764,208 @@ impl App {
         }
     }
 
+    /// Shows the prompt, or accepts it right away if the user has already approved the request
+    fn prompt(&self, prompt: Prompt, approved: bool) {
+        if approved {
+            prompt.accept();
+        } else {
+            self.prompt_sender
+                .send(prompt)
+                .expect("failed to send prompt");
+        }
+    }
+
     async fn handle_request(
         &self,
         request: InteractiveRequest,
         response_sender: oneshot::Sender<InteractiveResponse>,
     ) {
+        let (request, approved) = match request {
+            InteractiveRequest::Approved(request) => (*request, true),
+            request => (request, false),
+        };
         match request {
-            InteractiveRequest::EthRequestAccounts => {
-                response_sender
-                    .send(InteractiveResponse::EthRequestAccounts(
-                        if let Some(addr) = self.wallet_pane.active_account() {
-                            vec![addr]
-                        } else {
-                            vec![]
-                        },
-                    ))
-                    .inspect_err(|_| tracing::error!("failed to send eth_requestAccounts response"))
-                    .ok();
+            InteractiveRequest::EthRequestAccounts(origin) => {
+                match (self.wallet_pane.active_account(), origin) {
+                    // clients without an origin aren't web pages and don't need a grant
+                    (Some(account), Some(origin))
+                        if !self
+                            .config_tab
+                            .r_config()
+                            .is_origin_connected(&account, &origin) =>
+                    {
+                        let (sender, receiver) = oneshot::channel::<bool>();
+                        self.prompt_sender
+                            .send(Prompt::ConnectOrigin(origin.clone(), account, sender))
+                            .expect("failed to send connect origin prompt");
+                        let config_tab = self.config_tab.clone();
+                        tokio::spawn(async move {
+                            let approved = receiver
+                                .await
+                                .expect("failed to receive connect origin response");
+                            let accounts = if approved {
+                                config_tab
+                                    .update_config(|config| {
+                                        config.set_origin_connection(account, origin, true)
+                                    })
+                                    .inspect_err(|e| tracing::error!(?e, "failed to save config"))
+                                    .ok();
+                                Some(vec![account])
+                            } else {
+                                None
+                            };
+                            response_sender
+                                .send(InteractiveResponse::EthRequestAccounts(accounts))
+                                .inspect_err(|_| {
+                                    tracing::error!("failed to send eth_requestAccounts response")
+                                })
+                                .ok();
+                        });
+                    }
+                    (account, _) => {
+                        response_sender
+                            .send(InteractiveResponse::EthRequestAccounts(Some(
+                                account.into_iter().collect(),
+                            )))
+                            .inspect_err(|_| {
+                                tracing::error!("failed to send eth_requestAccounts response")
+                            })
+                            .ok();
+                    }
+                }
             }
-            InteractiveRequest::EthAccounts => {
+            InteractiveRequest::EthAccounts(origin) => {
                 response_sender
                     .send(InteractiveResponse::EthAccounts(
-                        if let Some(addr) = self.wallet_pane.active_account() {
-                            vec![addr]
-                        } else {
-                            vec![]
-                        },
+                        self.wallet_pane
+                            .active_account()
+                            .filter(|account| {
+                                origin.as_ref().is_none_or(|origin| {
+                                    self.config_tab
+                                        .r_config()
+                                        .is_origin_connected(account, origin)
+                                })
+                            })
+                            .into_iter()
+                            .collect(),
                     ))
                     .inspect_err(|_| tracing::error!("failed to send eth_accounts response"))
                     .ok();
             }
-            InteractiveRequest::SignTransaction(tx_req) => {
+            InteractiveRequest::WalletGetPermissions(origin) => {
+                response_sender
+                    .send(InteractiveResponse::WalletGetPermissions(match origin {
+                        Some(origin) => self.config_tab.r_config().origin_accounts(&origin),
+                        None => self.wallet_pane.active_account().into_iter().collect(),
+                    }))
+                    .inspect_err(|_| {
+                        tracing::error!("failed to send wallet_getPermissions response")
+                    })
+                    .ok();
+            }
+            InteractiveRequest::AuthorizeSigner(origin, signer) => {
+                let authorized = match origin {
+                    Some(origin) => self
+                        .config_tab
+                        .r_config()
+                        .is_origin_connected(&signer, &origin),
+                    // clients without an origin aren't web pages, any account can be used
+                    None => self.wallet_pane.has_signer(&signer),
+                };
+                response_sender
+                    .send(InteractiveResponse::AuthorizeSigner(authorized))
+                    .inspect_err(|_| tracing::error!("failed to send authorize signer response"))
+                    .ok();
+            }
+            InteractiveRequest::SignTransaction(origin, from, tx_req, simulation) => {
+                // editing the gas, fees or nonce doesn't change what the transaction does
+                let chain = tx_req
+                    .chain_id()
+                    .and_then(|id| NamedChain::try_from(id).ok());
+                let summary = summarize(
+                    &tx_req,
+                    simulation.as_ref(),
+                    chain,
+                    &self.config_tab.r_config(),
+                );
                 let (sender, receiver) =
                     oneshot::channel::<(Box<EthereumTypedTransaction<TxEip4844Variant>>, bool)>();
-                self.prompt_sender
-                    .send(Prompt::SendTransaction(tx_req, sender))
-                    .expect("failed to send send transaction prompt");
+                self.prompt(
+                    Prompt::SendTransaction(from, tx_req, simulation, sender),
+                    approved,
+                );
                 let wallet = self.wallet_pane.clone();
+                let history = self.history.clone();
                 tokio::spawn(async move {
                     let (tx, should_sign) = receiver
                         .await
                         .expect("failed to receive send transaction response");
                     if should_sign {
                         tracing::debug!("signing and sending transaction now");
+                        // the hash of the transaction as approved, its gas, fees or nonce may
+                        // have been edited
+                        let signature_hash = tx.signature_hash();
+                        let signature = wallet.sign_hash(Some(from), &signature_hash).await;
+                        // recorded whether the wallet or the client broadcasts it
+                        if let (Ok(signature), Some(chain)) = (&signature, chain) {
+                            history.record(TxRecord {
+                                chain,
+                                origin,
+                                from,
+                                hash: tx.tx_hash(signature),
+                                nonce: tx.nonce(),
+                                summary,
+                                status: TxStatus::Pending,
+                                signed_at: now(),
+                            });
+                        }
                         response_sender
                             .send(InteractiveResponse::SignTransaction(
-                                wallet
-                                    .sign_hash(None, &tx.signature_hash())
-                                    .await
-                                    .map_err(|e| {
-                                        tracing::error!(?e, "failed to sign tx");
-                                        let boxed_error: Box<dyn std::error::Error + Send + Sync> =
-                                            Box::new(e);
-                                        boxed_error
-                                    }),
+                                signature.map(|signature| (tx, signature)).map_err(|e| {
+                                    tracing::error!(?e, "failed to sign tx");
+                                    e.into()
+                                }),
                             ))
                             .expect("failed to send send transaction response");
                     } else {
                         tracing::debug!("sending transaction rejected");
                         response_sender
-                            .send(InteractiveResponse::SignTransaction(Err(Box::new(
-                                NexumTuiError::UserRejectedSigning,
-                            ))))
+                            .send(InteractiveResponse::SignTransaction(Err(
+                                NexumTuiError::UserRejectedSigning.into(),
+                            )))
                             .expect("failed to send send transaction response");
                     }
                 });
             }
+            // requests are approved once, the response sender is dropped
+            InteractiveRequest::Approved(_) => tracing::error!("nested approved request"),
+            InteractiveRequest::ApproveBatch(origin, calls) => {
+                let (sender, receiver) = oneshot::channel::<bool>();
+                self.prompt_sender
+                    .send(Prompt::Batch(origin, calls, sender))
+                    .expect("failed to send batch prompt");
+                tokio::spawn(async move {
+                    let approved = receiver.await.expect("failed to receive batch response");
+                    response_sender
+                        .send(InteractiveResponse::ApproveBatch(approved))
+                        .expect("failed to send batch response");
+                });
+            }
+            InteractiveRequest::WalletSendCalls(chain, from, calls) => {
+                let (sender, receiver) = oneshot::channel::<bool>();
+                self.prompt(Prompt::SendCalls(chain, from, calls, sender), approved);
+                tokio::spawn(async move {
+                    let approved = receiver
+                        .await
+                        .expect("failed to receive send calls response");
+                    response_sender
+                        .send(InteractiveResponse::WalletSendCalls(approved))
+                        .expect("failed to send send calls response");
+                });
+            }
             InteractiveRequest::EthSign(signer, message) => {
                 let (sender, receiver) = oneshot::channel::<(Address, Bytes, bool)>();
-                self.prompt_sender
-                    .send(Prompt::EthSign(signer, message, sender))
-                    .expect("failed to send eth_sign prompt");
+                self.prompt(Prompt::EthSign(signer, message, sender), approved);
                 let wallet = self.wallet_pane.clone();
                 tokio::spawn(async move {
                     let (signer, message, should_sign) =
@@ -640,27 +979,23 @@ impl App {
                                     .await
                                     .map_err(|e| {
                                         tracing::error!(?e, "failed to sign message");
-                                        let boxed_error: Box<dyn std::error::Error + Send + Sync> =
-                                            Box::new(e);
-                                        boxed_error
+                                        e.into()
                                     }),
                             ))
                             .expect("failed to send eth_sign response");
                     } else {
                         tracing::debug!("sending transaction rejected");
                         response_sender
-                            .send(InteractiveResponse::EthSign(Err(Box::new(
-                                NexumTuiError::UserRejectedSigning,
-                            ))))
+                            .send(InteractiveResponse::EthSign(Err(
+                                NexumTuiError::UserRejectedSigning.into(),
+                            )))
                             .expect("failed to send eth_sign response");
                     }
                 });
             }
             InteractiveRequest::EthSignTypedData(signer, message) => {
                 let (sender, receiver) = oneshot::channel::<(Address, Box<TypedData>, bool)>();
-                self.prompt_sender
-                    .send(Prompt::EthSignTypedData(signer, message, sender))
-                    .expect("failed to send eth_sign_typed_data prompt");
+                self.prompt(Prompt::EthSignTypedData(signer, message, sender), approved);
                 let wallet = self.wallet_pane.clone();
                 tokio::spawn(async move {
                     let (signer, message, should_sign) = receiver
@@ -675,26 +1010,186 @@ impl App {
                                     .await
                                     .map_err(|e| {
                                         tracing::error!(?e, "failed to sign typed data");
-                                        let boxed_error: Box<dyn std::error::Error + Send + Sync> =
-                                            Box::new(e);
-                                        boxed_error
+                                        e.into()
                                     }),
                             ))
                             .expect("failed to send eth_sign_typed_data response");
                     } else {
                         tracing::debug!("sending transaction rejected");
                         response_sender
-                            .send(InteractiveResponse::EthSignTypedData(Err(Box::new(
-                                NexumTuiError::UserRejectedSigning,
-                            ))))
+                            .send(InteractiveResponse::EthSignTypedData(Err(
+                                NexumTuiError::UserRejectedSigning.into(),
+                            )))
                             .expect("failed to send eth_sign_typed_data response");
                     }
                 });
             }
+            InteractiveRequest::EthSignLegacyTypedData(signer, message) => {
+                let (sender, receiver) =
+                    oneshot::channel::<(Address, Box<LegacyTypedData>, bool)>();
+                self.prompt(
+                    Prompt::EthSignLegacyTypedData(signer, message, sender),
+                    approved,
+                );
+                let wallet = self.wallet_pane.clone();
+                tokio::spawn(async move {
+                    let (signer, message, should_sign) = receiver
+                        .await
+                        .expect("failed to receive eth_sign_typed_data response");
+                    let signature = if should_sign {
+                        // the legacy format signs the hash as is, without an EIP-191 prefix
+                        match message.signature_hash() {
+                            Ok(hash) => wallet.sign_hash(Some(signer), &hash).await.map_err(|e| {
+                                tracing::error!(?e, "failed to sign typed data");
+                                e.into()
+                            }),
+                            Err(e) => Err(WalletError::InvalidParams(e.to_string())),
+                        }
+                    } else {
+                        tracing::debug!("signing typed data rejected");
+                        Err(NexumTuiError::UserRejectedSigning.into())
+                    };
+                    response_sender
+                        .send(InteractiveResponse::EthSignTypedData(signature))
+                        .expect("failed to send eth_sign_typed_data response");
+                });
+            }
+            InteractiveRequest::WalletAddEthereumChain(chain, rpc_urls) => {
+                let (sender, receiver) = oneshot::channel::<bool>();
+                self.prompt(Prompt::AddChain(chain, rpc_urls.clone(), sender), approved);
+                let config_tab = self.config_tab.clone();
+                tokio::spawn(async move {
+                    let approved = receiver
+                        .await
+                        .expect("failed to receive add chain response");
+                    tracing::debug!(?chain, approved, "add chain prompt answered");
+                    // the chain is served from the config, so it's shown in the Settings tab and
+                    // kept across restarts
+                    if approved {
+                        config_tab
+                            .update_config(|config| config.set_chain_rpcs(chain, rpc_urls))
+                            .inspect_err(|e| tracing::error!(?e, "failed to save config"))
+                            .ok();
+                    }
+                    response_sender
+                        .send(InteractiveResponse::WalletAddEthereumChain(approved))
+                        .expect("failed to send add chain response");
+                });
+            }
+            InteractiveRequest::WalletWatchAsset(chain, address, metadata) => {
+                // the token is already tracked, no need to bother the user again
+                if self.config_tab.r_config().token(&chain, &address) == Some(&metadata) {
+                    response_sender
+                        .send(InteractiveResponse::WalletWatchAsset(true))
+                        .inspect_err(|_| tracing::error!("failed to send watch asset response"))
+                        .ok();
+                    return;
+                }
+
+                let (sender, receiver) = oneshot::channel::<bool>();
+                self.prompt(
+                    Prompt::WatchAsset(chain, address, metadata.clone(), sender),
+                    approved,
+                );
+                let config_tab = self.config_tab.clone();
+                tokio::spawn(async move {
+                    let approved = receiver
+                        .await
+                        .expect("failed to receive watch asset response");
+                    if approved {
+                        config_tab
+                            .update_config(|config| config.track_token(chain, address, metadata))
+                            .inspect_err(|e| tracing::error!(?e, "failed to save config"))
+                            .ok();
+                    }
+                    response_sender
+                        .send(InteractiveResponse::WalletWatchAsset(approved))
+                        .inspect_err(|_| tracing::error!("failed to send watch asset response"))
+                        .ok();
+                });
+            }
         }
     }
 }
 
+/// Outcome of the simulation, colored by whether the transaction would succeed
+fn simulation_line(simulation: &Simulation) -> Line<'static> {
+    let color = match simulation {
+        Simulation::Success { .. } => Color::Green,
+        Simulation::Unavailable(_) => Color::Yellow,
+        _ => Color::Red,
+    };
+    Line::styled(simulation.to_string(), Style::default().fg(color))
+}
+
+/// Describes the change to the assets of the sender of a transaction, tracked tokens are shown
+/// with their symbol and decimals
+fn describe_asset_change(
+    change: &AssetChange,
+    chain: Option<NamedChain>,
+    config: &Config,
+) -> String {
+    match change {
+        AssetChange::Send { asset, to } => {
+            format!("you send {} to {to}", describe_asset(asset, chain, config))
+        }
+        AssetChange::Receive { asset, from } => {
+            format!(
+                "you receive {} from {from}",
+                describe_asset(asset, chain, config)
+            )
+        }
+        AssetChange::Approve { asset, spender } => format!(
+            "you approve {spender} to spend {}",
+            describe_asset(asset, chain, config)
+        ),
+    }
+}
+
+fn describe_asset(asset: &Asset, chain: Option<NamedChain>, config: &Config) -> String {
+    let metadata = |token: &Address| chain.and_then(|chain| config.token(&chain, token));
+    let name = |token: &Address| {
+        metadata(token).map_or_else(|| token.to_string(), |metadata| metadata.symbol.clone())
+    };
+    match asset {
+        Asset::Native(amount) => format!("{} {}", format_amount(*amount, 18), native_symbol(chain)),
+        Asset::Erc20 { token, amount } => match (metadata(token), *amount == U256::MAX) {
+            (Some(metadata), true) => format!("unlimited {}", metadata.symbol),
+            (Some(metadata), false) => format!(
+                "{} {}",
+                format_amount(*amount, metadata.decimals),
+                metadata.symbol
+            ),
+            (None, true) => format!("unlimited {token}"),
+            (None, false) => format!("{amount} units of {token}"),
+        },
+        Asset::Erc721 { token, id } => format!("#{id} of {}", name(token)),
+        Asset::Erc1155 { token, id, amount } => format!("{amount} of #{id} of {}", name(token)),
+        Asset::All(token) => format!("every token of {}", name(token)),
+    }
+}
+
+/// Formats the amount in units of the token, without trailing zeros
+fn format_amount(amount: U256, decimals: u8) -> String {
+    match format_units(amount, decimals) {
+        Ok(amount) if amount.contains('.') => amount
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+        _ => amount.to_string(),
+    }
+}
+
+/// Symbol of the native currency of the chain
+const fn native_symbol(chain: Option<NamedChain>) -> &'static str {
+    match chain {
+        Some(NamedChain::Gnosis) => "xDAI",
+        Some(NamedChain::Polygon) => "POL",
+        Some(NamedChain::BinanceSmartChain) => "BNB",
+        _ => "ETH",
+    }
+}
+
 pub trait HandleEvent {
     fn handle_key(&self, event: &KeyEvent);
 }
@@ -702,8 +1197,11 @@ pub trait HandleEvent {
 enum Prompt {
     AccountUnlock(String),
     AccountUnlockInvalidPasswordRetry(String),
+    /// Sign the transaction, the outcome of its simulation is shown above it
     SendTransaction(
+        Address,
         Box<EthereumTypedTransaction<TxEip4844Variant>>,
+        Option<Simulation>,
         oneshot::Sender<(Box<EthereumTypedTransaction<TxEip4844Variant>>, bool)>,
     ),
     EthSign(Address, Bytes, oneshot::Sender<(Address, Bytes, bool)>),
@@ -712,6 +1210,55 @@ enum Prompt {
         Box<TypedData>,
         oneshot::Sender<(Address, Box<TypedData>, bool)>,
     ),
+    /// Grant the origin access to the account
+    ConnectOrigin(Url, Address, oneshot::Sender<bool>),
+    AddChain(NamedChain, Vec<Url>, oneshot::Sender<bool>),
+    EthSignLegacyTypedData(
+        Address,
+        Box<LegacyTypedData>,
+        oneshot::Sender<(Address, Box<LegacyTypedData>, bool)>,
+    ),
+    /// Send the whole batch of calls from the account
+    SendCalls(
+        NamedChain,
+        Address,
+        Vec<TransactionRequest>,
+        oneshot::Sender<bool>,
+    ),
+    /// Track the ERC-20 token on the chain
+    WatchAsset(NamedChain, Address, TokenMetadata, oneshot::Sender<bool>),
+    /// Approve all the interactive calls of a JSON-RPC batch at once
+    Batch(Option<Url>, Vec<BatchCall>, oneshot::Sender<bool>),
+}
+
+impl Prompt {
+    /// Answers the prompt as if the user accepted it
+    fn accept(self) {
+        self.answer(true)
+    }
+
+    /// Answers the prompt with the decision of the user. The request may have been given up
+    /// already, e.g. its client disconnected, then there's no one left to answer.
+    fn answer(self, accepted: bool) {
+        match self {
+            Self::AccountUnlock(_) | Self::AccountUnlockInvalidPasswordRetry(_) => {}
+            Self::SendTransaction(_, tx, _, sender) => _ = sender.send((tx, accepted)),
+            Self::EthSign(signer_addr, message, sender) => {
+                _ = sender.send((signer_addr, message, accepted))
+            }
+            Self::EthSignTypedData(signer_addr, data, sender) => {
+                _ = sender.send((signer_addr, data, accepted))
+            }
+            Self::EthSignLegacyTypedData(signer_addr, data, sender) => {
+                _ = sender.send((signer_addr, data, accepted))
+            }
+            Self::ConnectOrigin(_, _, sender)
+            | Self::AddChain(_, _, sender)
+            | Self::SendCalls(_, _, _, sender)
+            | Self::WatchAsset(_, _, _, sender)
+            | Self::Batch(_, _, sender) => _ = sender.send(accepted),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -720,7 +1267,18 @@ struct WalletPane {
     accounts: RwLock<Vec<NexumAccount>>,
     list_state: RwLock<ListState>,
     active_wallet_idx: RwLock<Option<usize>>,
+    /// Account the password prompt is shown for
+    unlocking: RwLock<Option<Unlock>>,
     prompt_sender: mpsc::UnboundedSender<Prompt>,
+    /// Notified when the active account changes
+    status: Status,
+}
+
+#[derive(Debug)]
+struct Unlock {
+    idx: usize,
+    /// Notified whether the account got unlocked, if a signing request is waiting on it
+    unlocked: Option<oneshot::Sender<bool>>,
 }
 
 impl Widget for &WalletPane {
@@ -771,32 +1329,14 @@ macro_rules! delegate_sign_to_account {
             &self,
             from: Option<Address>,
             $param_name: &$param_type,
-        ) -> alloy::signers::Result<Signature> {
-            if let Some(idx) = {
-                // wrapped into a block expresion so the guard can drop after read and before
-                // awaiting on sign_hash
-                let val = *self.r_active_wallet_idx();
-                val
-            } {
-                // TODO: maybe figure out how to do this without cloning the account
-                // right now the accounts shouldn't be that expensive to clone, would just
-                // be the signer object
-                let account = self.r_accounts()[idx].clone();
-
-                if from.is_some() && from != account.address() {
-                    return Err(alloy::signers::Error::Other(Box::new(
-                        NexumTuiError::SignerDoesntMatch,
-                    )));
-                }
+        ) -> Result<Signature, NexumTuiError> {
+            let account = self.signer_account(from).await?;
 
-                account.$method_name($param_name).await.map_err(|e| {
-                    alloy::signers::Error::Other(Box::new(Into::<NexumTuiError>::into(e)))
-                })
-            } else {
-                Err(alloy::signers::Error::Other(Box::new(
-                    NexumTuiError::NoActiveWallet,
-                )))
+            if from.is_some() && from != account.address() {
+                return Err(NexumTuiError::SignerDoesntMatch);
             }
+
+            Ok(account.$method_name($param_name).await?)
         }
     };
 }
@@ -851,13 +1391,15 @@ impl WalletPane {
             .expect("failed to get read lock on list state");
         let idx = list_state.selected();
         *self.w_active_wallet_idx() = idx;
+        self.status.changed();
         idx
     }
 
     fn on_prompt_input(&self, input: String) {
-        if let Some(idx) = *self.r_active_wallet_idx()
-            && { self.r_accounts()[idx].is_locked() }
-        {
+        let Some(idx) = self.r_unlocking().as_ref().map(|unlock| unlock.idx) else {
+            return;
+        };
+        if self.r_accounts()[idx].is_locked() {
             let account = &mut self.w_accounts()[idx];
             if account.try_unlock(input).is_err() {
                 self.prompt_sender
@@ -865,8 +1407,72 @@ impl WalletPane {
                         account.name().to_string(),
                     ))
                     .expect("sending password retry prompt failed");
+                return;
+            }
+        }
+        if let Some(Unlock {
+            unlocked: Some(unlocked),
+            ..
+        }) = self.w_unlocking().take()
+        {
+            unlocked.send(true).ok();
+        }
+    }
+
+    fn on_prompt_cancel(&self) {
+        if let Some(Unlock {
+            unlocked: Some(unlocked),
+            ..
+        }) = self.w_unlocking().take()
+        {
+            unlocked.send(false).ok();
+        }
+    }
+
+    /// Returns whether any of the accounts, locked or not, signs for the address
+    fn has_signer(&self, address: &Address) -> bool {
+        self.r_accounts()
+            .iter()
+            .any(|account| account.signs_for(address))
+    }
+
+    /// Returns the account signing for `from`, or the active one if not set. Locked accounts
+    /// are unlocked first, prompting for the password.
+    async fn signer_account(&self, from: Option<Address>) -> Result<NexumAccount, NexumTuiError> {
+        let idx = match from {
+            Some(from) => self
+                .r_accounts()
+                .iter()
+                .position(|account| account.signs_for(&from))
+                .ok_or(NexumTuiError::UnknownSigner(from))?,
+            None => {
+                let idx = *self.r_active_wallet_idx();
+                idx.ok_or(NexumTuiError::NoActiveWallet)?
+            }
+        };
+
+        let name = {
+            let account = &self.r_accounts()[idx];
+            account.is_locked().then(|| account.name().to_string())
+        };
+        if let Some(name) = name {
+            let (sender, receiver) = oneshot::channel();
+            *self.w_unlocking() = Some(Unlock {
+                idx,
+                unlocked: Some(sender),
+            });
+            self.prompt_sender
+                .send(Prompt::AccountUnlock(name))
+                .expect("sending password prompt request failed");
+            if !receiver.await.unwrap_or_default() {
+                return Err(NexumTuiError::AccountLocked);
             }
         }
+
+        // TODO: maybe figure out how to do this without cloning the account
+        // right now the accounts shouldn't be that expensive to clone, would just
+        // be the signer object
+        Ok(self.r_accounts()[idx].clone())
     }
 
     fn active_account(&self) -> Option<Address> {
@@ -905,6 +1511,18 @@ impl WalletPane {
             .expect("failed to get write lock on active wallet idx")
     }
 
+    fn r_unlocking(&self) -> RwLockReadGuard<'_, Option<Unlock>> {
+        self.unlocking
+            .read()
+            .expect("failed to get read lock on unlocking")
+    }
+
+    fn w_unlocking(&self) -> RwLockWriteGuard<'_, Option<Unlock>> {
+        self.unlocking
+            .write()
+            .expect("failed to get write lock on unlocking")
+    }
+
     fn r_is_active(&self) -> RwLockReadGuard<'_, bool> {
         self.is_active
             .read()
@@ -936,6 +1554,10 @@ impl HandleEvent for WalletPane {
                 if let Some(idx) = self.set_active_wallet_to_selected_index()
                     && self.r_accounts()[idx].is_locked()
                 {
+                    *self.w_unlocking() = Some(Unlock {
+                        idx,
+                        unlocked: None,
+                    });
                     let account = &self.r_accounts()[idx];
                     self.prompt_sender
                         .send(Prompt::AccountUnlock(account.name().to_string()))
@@ -952,9 +1574,15 @@ enum NexumTuiError {
     /// User rejected signing the transaction, message or typed data
     #[error("user rejected signing")]
     UserRejectedSigning,
-    /// Generally means that the signing address and the active wallet address don't match
+    /// Generally means that the signing address and the unlocked wallet address don't match
     #[error("signer doesnt match")]
     SignerDoesntMatch,
+    /// None of the accounts signs for the address
+    #[error("no account for signer {0}")]
+    UnknownSigner(Address),
+    /// The user didn't unlock the signing account
+    #[error("account is locked")]
+    AccountLocked,
     /// No active wallet
     #[error("no active wallet")]
     NoActiveWallet,
@@ -962,3 +1590,63 @@ enum NexumTuiError {
     #[error("signing error")]
     SigningError(#[from] eyre::Report),
 }
+
+impl From<NexumTuiError> for WalletError {
+    fn from(err: NexumTuiError) -> Self {
+        match err {
+            NexumTuiError::UserRejectedSigning | NexumTuiError::AccountLocked => Self::UserRejected,
+            NexumTuiError::SignerDoesntMatch | NexumTuiError::UnknownSigner(_) => {
+                Self::Unauthorized
+            }
+            NexumTuiError::NoActiveWallet => Self::Disconnected,
+            NexumTuiError::SigningError(e) => Self::internal(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        url.parse().unwrap()
+    }
+
+    #[test]
+    fn cli_rpcs_override_the_config_rpcs_of_their_chain() {
+        let config_rpcs = vec![
+            (
+                NamedChain::Mainnet,
+                vec![url("https://eth.example"), url("https://eth2.example")],
+            ),
+            (NamedChain::Sepolia, vec![url("https://sepolia.example")]),
+        ];
+        let cli_rpcs = [
+            (NamedChain::Mainnet, url("http://localhost:8545")),
+            (NamedChain::Mainnet, url("http://localhost:8546")),
+            (NamedChain::Base, url("https://base.example")),
+        ];
+
+        let rpcs = server_rpcs(config_rpcs, &cli_rpcs);
+        assert_eq!(
+            rpcs,
+            HashMap::from([
+                (
+                    NamedChain::Mainnet,
+                    vec![url("http://localhost:8545"), url("http://localhost:8546")],
+                ),
+                (NamedChain::Sepolia, vec![url("https://sepolia.example")]),
+                (NamedChain::Base, vec![url("https://base.example")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn config_rpcs_are_served_without_cli_rpcs() {
+        let config_rpcs = vec![(NamedChain::Mainnet, vec![url("https://eth.example")])];
+        assert_eq!(
+            server_rpcs(config_rpcs.clone(), &[]),
+            config_rpcs.into_iter().collect()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use std::{path::PathBuf, time::Instant};
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use alloy::{
     dyn_abi::TypedData,
@@ -11,6 +14,7 @@ use alloy::{
     },
 };
 use eyre::OptionExt;
+use serde::Deserialize;
 
 #[derive(Debug, Clone)]
 pub struct NexumAccount {
@@ -51,6 +55,15 @@ impl NexumAccount {
         self.signer.address()
     }
 
+    /// Returns whether the account signs for the address. Unlike [`NexumAccount::address`]
+    /// this also knows the address of locked keystores.
+    pub fn signs_for(&self, address: &Address) -> bool {
+        match &self.signer {
+            NexumSigner::Keystore(path, None) => keystore_address(path).as_ref() == Some(address),
+            signer => signer.address().as_ref() == Some(address),
+        }
+    }
+
     pub async fn sign_hash(&self, hash: &B256) -> eyre::Result<Signature> {
         self.signer.sign_hash(hash).await
     }
@@ -158,6 +171,19 @@ impl NexumSigner {
     }
 }
 
+/// Reads the address stored in plain text in the keystore file, without decrypting it
+fn keystore_address(path: &Path) -> Option<Address> {
+    #[derive(Deserialize)]
+    struct Keystore {
+        address: Address,
+    }
+
+    let keystore = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str::<Keystore>(&keystore)
+        .ok()
+        .map(|keystore| keystore.address)
+}
+
 pub fn load_keystores(dir: &str, ignored: &[&str]) -> eyre::Result<Vec<NexumAccount>> {
     let dir = if dir.starts_with("~/")
         && let Some((_, path_rel_to_home)) = dir.split_once("~/")
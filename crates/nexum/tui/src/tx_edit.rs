@@ -0,0 +1,348 @@
+use alloy::{
+    consensus::{EthereumTypedTransaction, Transaction, TxEip4844Variant},
+    primitives::{
+        U256,
+        utils::{ParseUnits, parse_units},
+    },
+};
+use alloy_chains::NamedChain;
+use ratatui::{
+    style::{Color, Style},
+    text::Line,
+};
+
+use crate::{format_amount, native_symbol};
+
+type TypedTransaction = EthereumTypedTransaction<TxEip4844Variant>;
+
+/// Gas, fees and nonce of a transaction, the fields the user may edit before approving it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxFees {
+    pub gas_limit: u64,
+    /// Max fee per gas, or gas price of transactions without EIP-1559 fees, in wei
+    pub max_fee: u128,
+    /// Max priority fee per gas in wei, `None` for transactions without EIP-1559 fees
+    pub priority_fee: Option<u128>,
+    pub nonce: u64,
+}
+
+impl TxFees {
+    pub fn of(tx: &TypedTransaction) -> Self {
+        Self {
+            gas_limit: tx.gas_limit(),
+            max_fee: tx.max_fee_per_gas(),
+            priority_fee: tx.max_priority_fee_per_gas(),
+            nonce: tx.nonce(),
+        }
+    }
+
+    /// Sets the gas, fees and nonce of the transaction, which changes its signature hash
+    pub fn apply(self, tx: &mut TypedTransaction) {
+        macro_rules! set_eip1559_fees {
+            ($tx:expr) => {{
+                $tx.gas_limit = self.gas_limit;
+                $tx.max_fee_per_gas = self.max_fee;
+                $tx.max_priority_fee_per_gas =
+                    self.priority_fee.unwrap_or($tx.max_priority_fee_per_gas);
+                $tx.nonce = self.nonce;
+            }};
+        }
+        match tx {
+            EthereumTypedTransaction::Legacy(tx) => {
+                tx.gas_limit = self.gas_limit;
+                tx.gas_price = self.max_fee;
+                tx.nonce = self.nonce;
+            }
+            EthereumTypedTransaction::Eip2930(tx) => {
+                tx.gas_limit = self.gas_limit;
+                tx.gas_price = self.max_fee;
+                tx.nonce = self.nonce;
+            }
+            EthereumTypedTransaction::Eip1559(tx) => set_eip1559_fees!(tx),
+            EthereumTypedTransaction::Eip4844(tx) => set_eip1559_fees!(tx.as_mut()),
+            EthereumTypedTransaction::Eip7702(tx) => set_eip1559_fees!(tx),
+        }
+    }
+
+    /// Most the transaction may pay for its gas in wei, including the blob gas of blob
+    /// transactions
+    fn max_gas_cost(&self, tx: &TypedTransaction) -> U256 {
+        let blob_gas_cost = U256::from(tx.blob_gas_used().unwrap_or_default())
+            * U256::from(tx.max_fee_per_blob_gas().unwrap_or_default());
+        U256::from(self.gas_limit) * U256::from(self.max_fee) + blob_gas_cost
+    }
+}
+
+/// Field of the transaction being edited
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TxField {
+    GasLimit,
+    MaxFee,
+    PriorityFee,
+    Nonce,
+}
+
+impl TxField {
+    /// Fields of the transaction, transactions without EIP-1559 fees have no priority fee
+    fn all(fees: &TxFees) -> Vec<Self> {
+        [Self::GasLimit, Self::MaxFee, Self::PriorityFee, Self::Nonce]
+            .into_iter()
+            .filter(|field| *field != Self::PriorityFee || fees.priority_fee.is_some())
+            .collect()
+    }
+
+    fn name(self, fees: &TxFees) -> &'static str {
+        match self {
+            Self::GasLimit => "Gas limit",
+            Self::MaxFee if fees.priority_fee.is_none() => "Gas price",
+            Self::MaxFee => "Max fee",
+            Self::PriorityFee => "Priority fee",
+            Self::Nonce => "Nonce",
+        }
+    }
+
+    /// Value of the field as typed in by the user, fees are in gwei
+    fn input(self, fees: &TxFees) -> String {
+        match self {
+            Self::GasLimit => fees.gas_limit.to_string(),
+            Self::MaxFee => format_gwei(fees.max_fee),
+            Self::PriorityFee => fees.priority_fee.map(format_gwei).unwrap_or_default(),
+            Self::Nonce => fees.nonce.to_string(),
+        }
+    }
+
+    /// Fees with the field set to the input
+    fn parse(self, input: &str, mut fees: TxFees) -> Result<TxFees, String> {
+        let input = input.trim();
+        match self {
+            Self::GasLimit => {
+                fees.gas_limit = input.parse().map_err(|_| "invalid gas limit")?;
+            }
+            Self::MaxFee => fees.max_fee = parse_gwei(input)?,
+            Self::PriorityFee => fees.priority_fee = Some(parse_gwei(input)?),
+            Self::Nonce => fees.nonce = input.parse().map_err(|_| "invalid nonce")?,
+        }
+        match fees.priority_fee {
+            Some(priority_fee) if priority_fee > fees.max_fee => {
+                Err("priority fee is higher than max fee".to_string())
+            }
+            _ => Ok(fees),
+        }
+    }
+}
+
+/// Edit of the gas, fees and nonce of a transaction in its approval prompt, one field at a time
+#[derive(Debug)]
+pub struct TxEdit {
+    fees: TxFees,
+    field: TxField,
+    input: String,
+    error: Option<String>,
+}
+
+impl TxEdit {
+    pub fn new(tx: &TypedTransaction) -> Self {
+        let fees = TxFees::of(tx);
+        Self {
+            input: TxField::GasLimit.input(&fees),
+            fees,
+            field: TxField::GasLimit,
+            error: None,
+        }
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.input.push(ch);
+        self.error = None;
+    }
+
+    pub fn pop(&mut self) {
+        self.input.pop();
+        self.error = None;
+    }
+
+    /// Moves to the next field, `forward` or back, keeping the input of the current one if it's
+    /// valid
+    pub fn select(&mut self, forward: bool) {
+        if !self.commit() {
+            return;
+        }
+        let fields = TxField::all(&self.fees);
+        let idx = fields
+            .iter()
+            .position(|field| *field == self.field)
+            .unwrap_or_default();
+        let idx = if forward {
+            (idx + 1) % fields.len()
+        } else {
+            (idx + fields.len() - 1) % fields.len()
+        };
+        self.field = fields[idx];
+        self.input = self.field.input(&self.fees);
+    }
+
+    /// Gas, fees and nonce with the input of the current field, `None` if it's invalid
+    pub fn finish(&mut self) -> Option<TxFees> {
+        self.commit().then_some(self.fees)
+    }
+
+    fn commit(&mut self) -> bool {
+        match self.field.parse(&self.input, self.fees) {
+            Ok(fees) => {
+                self.fees = fees;
+                true
+            }
+            Err(error) => {
+                self.error = Some(error);
+                false
+            }
+        }
+    }
+}
+
+/// Gas, fees and nonce of the transaction followed by the most it may cost, as being edited if
+/// it is
+pub fn fee_lines(
+    tx: &TypedTransaction,
+    edit: Option<&TxEdit>,
+    chain: Option<NamedChain>,
+) -> Vec<Line<'static>> {
+    // the breakdown follows the input as it's typed in
+    let fees = edit.map_or_else(
+        || TxFees::of(tx),
+        |edit| {
+            edit.field
+                .parse(&edit.input, edit.fees)
+                .unwrap_or(edit.fees)
+        },
+    );
+    // the field being edited is marked, the others are indented to line up with it
+    let indent = if edit.is_some() { "  " } else { "" };
+    let mut lines = TxField::all(&fees)
+        .into_iter()
+        .map(|field| {
+            let unit = match field {
+                TxField::MaxFee | TxField::PriorityFee => " gwei",
+                TxField::GasLimit | TxField::Nonce => "",
+            };
+            match edit {
+                Some(edit) if edit.field == field => Line::styled(
+                    format!("> {:<13} {}{unit}", field.name(&fees), edit.input),
+                    Style::default().fg(Color::Blue),
+                ),
+                _ => Line::from(format!(
+                    "{indent}{:<13} {}{unit}",
+                    field.name(&fees),
+                    field.input(&fees)
+                )),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let symbol = native_symbol(chain);
+    let gas_cost = fees.max_gas_cost(tx);
+    lines.push(Line::from(format!(
+        "{indent}{:<13} {} {symbol} gas + {} {symbol} value = {} {symbol}",
+        "Max cost",
+        format_amount(gas_cost, 18),
+        format_amount(tx.value(), 18),
+        format_amount(gas_cost.saturating_add(tx.value()), 18),
+    )));
+    if let Some(error) = edit.and_then(|edit| edit.error.as_ref()) {
+        lines.push(Line::styled(error.clone(), Style::default().fg(Color::Red)));
+    }
+    lines
+}
+
+fn format_gwei(fee: u128) -> String {
+    format_amount(U256::from(fee), 9)
+}
+
+fn parse_gwei(input: &str) -> Result<u128, String> {
+    match parse_units(input, "gwei") {
+        Ok(ParseUnits::U256(fee)) => u128::try_from(fee).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("invalid fee {input}, in gwei"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn eip1559_fees() -> TxFees {
+        TxFees {
+            gas_limit: 21_000,
+            max_fee: 30 * GWEI,
+            priority_fee: Some(2 * GWEI),
+            nonce: 7,
+        }
+    }
+
+    #[test]
+    fn parses_gwei() {
+        assert_eq!(parse_gwei("30"), Ok(30 * GWEI));
+        assert_eq!(parse_gwei("1.5"), Ok(1_500_000_000));
+        assert_eq!(parse_gwei("0.000000001"), Ok(1));
+        // fractions of a wei are dropped
+        assert_eq!(parse_gwei("1.0000000001"), Ok(GWEI));
+        assert!(parse_gwei("-1").is_err());
+        assert!(parse_gwei("fast").is_err());
+        assert_eq!(parse_gwei(&format_gwei(1_234_567_891)), Ok(1_234_567_891));
+    }
+
+    #[test]
+    fn parses_fields() {
+        let fees = eip1559_fees();
+        assert_eq!(
+            TxField::GasLimit.parse(" 50000 ", fees),
+            Ok(TxFees {
+                gas_limit: 50_000,
+                ..fees
+            })
+        );
+        assert_eq!(
+            TxField::Nonce.parse("8", fees),
+            Ok(TxFees { nonce: 8, ..fees })
+        );
+        assert_eq!(
+            TxField::MaxFee.parse("40.5", fees),
+            Ok(TxFees {
+                max_fee: 40_500_000_000,
+                ..fees
+            })
+        );
+        assert_eq!(
+            TxField::PriorityFee.parse("3", fees),
+            Ok(TxFees {
+                priority_fee: Some(3 * GWEI),
+                ..fees
+            })
+        );
+        assert!(TxField::GasLimit.parse("-1", fees).is_err());
+        assert!(TxField::Nonce.parse("next", fees).is_err());
+    }
+
+    #[test]
+    fn priority_fee_stays_below_max_fee() {
+        let fees = eip1559_fees();
+        assert!(TxField::PriorityFee.parse("31", fees).is_err());
+        assert!(TxField::MaxFee.parse("1", fees).is_err());
+
+        // transactions without EIP-1559 fees only have a gas price
+        let legacy = TxFees {
+            priority_fee: None,
+            ..fees
+        };
+        assert_eq!(
+            TxField::MaxFee.parse("1", legacy),
+            Ok(TxFees {
+                max_fee: GWEI,
+                ..legacy
+            })
+        );
+        assert_eq!(TxField::all(&legacy).len(), 3);
+    }
+}